@@ -0,0 +1,930 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+
+use gif::{DisposalMethod, Repeat};
+use image::{imageops, ImageBuffer, Rgba, RgbaImage};
+
+/// A frame as the GIF stores it: a patch drawn over the previous frames at
+/// `left`, `top`, rather than the whole canvas. WebP frames are decoded as
+/// whole canvases.
+#[derive(Clone, Debug)]
+pub struct Frame {
+    pub image: RgbaImage,
+    pub left: u32,
+    pub top: u32,
+    /// in milliseconds; GIF rounds it to hundredths of a second
+    pub delay: u32,
+    /// what happens to the patch before the next frame is drawn
+    pub dispose: DisposalMethod,
+}
+
+#[derive(Clone, Debug)]
+pub struct Animation {
+    pub width: u32,
+    pub height: u32,
+    pub frames: Vec<Frame>,
+}
+
+/// How `Options` changes the speed of an animation.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Retime {
+    /// every frame shown for `1 / fps` seconds
+    Fps(f64),
+    /// each frame's delay divided by this, so `2.0` plays twice as fast
+    Speed(f64),
+}
+
+/// What `resize_animation` encodes to.
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Format {
+    /// 1-bit transparency, at most 256 colors per frame
+    #[default]
+    Gif,
+    /// lossy, with full alpha
+    Webp,
+    /// lossless, with full alpha
+    Apng,
+    /// vector, so passed through as is
+    LottieJson,
+}
+
+impl Format {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Format::Gif => "gif",
+            Format::Webp => "webp",
+            Format::Apng => "png",
+            Format::LottieJson => "json",
+        }
+    }
+}
+
+/// The resampling filter, from fastest and blockiest to slowest and
+/// sharpest.
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Filter {
+    Nearest,
+    Triangle,
+    CatmullRom,
+    Gaussian,
+    #[default]
+    Lanczos3,
+}
+
+impl From<Filter> for imageops::FilterType {
+    fn from(filter: Filter) -> Self {
+        match filter {
+            Filter::Nearest => imageops::FilterType::Nearest,
+            Filter::Triangle => imageops::FilterType::Triangle,
+            Filter::CatmullRom => imageops::FilterType::CatmullRom,
+            Filter::Gaussian => imageops::FilterType::Gaussian,
+            Filter::Lanczos3 => imageops::FilterType::Lanczos3,
+        }
+    }
+}
+
+/// How an animation that isn't the shape of the new size is scaled.
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Aspect {
+    /// scale to fit inside, centered, padding the rest with transparency
+    #[default]
+    Fit,
+    /// scale to cover, centered, cropping what's outside
+    Fill,
+    /// scale the width and height separately
+    Stretch,
+}
+
+#[derive(Clone, Debug)]
+pub struct Options {
+    pub format: Format,
+    /// width and height of the resized animation, in pixels
+    pub size: u32,
+    pub filter: Filter,
+    pub aspect: Aspect,
+    /// keep the original frame delays when `None`
+    pub retime: Option<Retime>,
+    /// largest file size, in bytes; see `Reduction`
+    pub budget: Option<u64>,
+}
+
+/// What was given up to fit an animation in `Options.budget`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Reduction {
+    pub frames: usize,
+    pub original_frames: usize,
+    /// colors per frame, at most 256; only GIFs are reduced
+    pub colors: usize,
+    pub size: u32,
+    pub original_size: u32,
+    pub bytes: usize,
+    /// false when even the most reduced version is over budget
+    pub fits: bool,
+}
+
+impl Reduction {
+    /// Whether the animation fit without giving anything up.
+    pub fn is_lossless(&self) -> bool {
+        self.frames == self.original_frames
+            && self.colors == MAX_COLORS
+            && self.size == self.original_size
+    }
+}
+
+impl fmt::Display for Reduction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut sacrifices = Vec::new();
+        if self.frames < self.original_frames {
+            sacrifices.push(format!(
+                "{} of {} frames",
+                self.frames, self.original_frames
+            ));
+        }
+        if self.colors < MAX_COLORS {
+            sacrifices.push(format!("{} colors", self.colors));
+        }
+        if self.size < self.original_size {
+            sacrifices.push(format!("{}px", self.size));
+        }
+        if sacrifices.is_empty() {
+            sacrifices.push("nothing reduced".to_owned());
+        }
+        write!(f, "{}, {} bytes", sacrifices.join(", "), self.bytes)
+    }
+}
+
+/// An animation from `resize_animation`.
+pub struct Encoded {
+    pub bytes: Vec<u8>,
+    /// `None` without a budget
+    pub reduction: Option<Reduction>,
+}
+
+const MAX_COLORS: usize = 256;
+
+/// How far `resize_animation` has gone to fit a budget. Each step reduces the
+/// next of colors, frames and size that can still be reduced, so no one of
+/// them suffers much more than the others.
+struct Level {
+    colors: usize,
+    keep_every: usize,
+    scale: f64,
+    turn: usize,
+    /// false for formats without a palette
+    reduce_colors: bool,
+}
+
+impl Level {
+    const COLORS: [usize; 5] = [MAX_COLORS, 128, 64, 32, 16];
+    const KEEP_EVERY: [usize; 4] = [1, 2, 3, 4];
+    const SCALES: [f64; 5] = [1.0, 0.875, 0.75, 0.625, 0.5];
+
+    fn new(reduce_colors: bool) -> Self {
+        Level {
+            colors: MAX_COLORS,
+            keep_every: 1,
+            scale: 1.0,
+            turn: 0,
+            reduce_colors,
+        }
+    }
+
+    /// Moves to the next level, or returns false when everything is as
+    /// reduced as it goes.
+    fn next(&mut self) -> bool {
+        fn advance<T: Copy + PartialEq>(steps: &[T], value: &mut T) -> bool {
+            match steps.iter().position(|step| step == value) {
+                Some(i) if i + 1 < steps.len() => {
+                    *value = steps[i + 1];
+                    true
+                }
+                _ => false,
+            }
+        }
+        for _ in 0..3 {
+            let turn = self.turn;
+            self.turn = (self.turn + 1) % 3;
+            let advanced = match turn {
+                0 => self.reduce_colors && advance(&Self::COLORS, &mut self.colors),
+                1 => advance(&Self::KEEP_EVERY, &mut self.keep_every),
+                _ => advance(&Self::SCALES, &mut self.scale),
+            };
+            if advanced {
+                return true;
+            }
+        }
+        false
+    }
+}
+
+/// GIF indexes for `pixels` (RGBA, with alpha either 0 or 255): the indexes,
+/// the RGB palette of at most `colors`, and the transparent index.
+fn quantize(pixels: &[u8], colors: usize) -> (Vec<u8>, Vec<u8>, Option<u8>) {
+    let opaque: Vec<u8> = pixels
+        .chunks_exact(4)
+        .filter(|pixel| pixel[3] != 0)
+        .flatten()
+        .copied()
+        .collect();
+    let has_transparent = opaque.len() < pixels.len();
+    // the transparent index takes up one of the colors
+    let colors = if has_transparent { colors - 1 } else { colors };
+
+    let mut exact: HashMap<&[u8], u8> = HashMap::new();
+    for pixel in opaque.chunks_exact(4) {
+        if exact.len() > colors {
+            break;
+        }
+        let index = exact.len() as u8;
+        exact.entry(pixel).or_insert(index);
+    }
+
+    let (mut palette, quant) = if exact.len() <= colors {
+        let mut palette = vec![0; exact.len() * 3];
+        for (pixel, &index) in &exact {
+            palette[index as usize * 3..index as usize * 3 + 3].copy_from_slice(&pixel[..3]);
+        }
+        (palette, None)
+    } else {
+        let quant = color_quant::NeuQuant::new(10, colors, &opaque);
+        (quant.color_map_rgb(), Some(quant))
+    };
+    let index_of = |pixel: &[u8]| match &quant {
+        None => exact[pixel],
+        Some(quant) => quant.index_of(pixel) as u8,
+    };
+
+    let transparent = has_transparent.then(|| {
+        palette.extend_from_slice(&[0, 0, 0]);
+        (palette.len() / 3 - 1) as u8
+    });
+    let indexes = pixels
+        .chunks_exact(4)
+        .map(|pixel| match transparent {
+            Some(transparent) if pixel[3] == 0 => transparent,
+            _ => index_of(pixel),
+        })
+        .collect();
+    (indexes, palette, transparent)
+}
+
+/// Resizes `image` with its colors premultiplied by alpha, so the colors of
+/// transparent pixels (often black) don't bleed into the edges.
+pub fn resize_premultiplied(
+    image: &RgbaImage,
+    width: u32,
+    height: u32,
+    filter: Filter,
+) -> RgbaImage {
+    let premultiplied = ImageBuffer::from_fn(image.width(), image.height(), |x, y| {
+        let [r, g, b, a] = image.get_pixel(x, y).0.map(|c| c as f32 / 255.0);
+        Rgba([r * a, g * a, b * a, a])
+    });
+    let resized = imageops::resize(&premultiplied, width, height, filter.into());
+    RgbaImage::from_fn(width, height, |x, y| {
+        let [r, g, b, a] = resized.get_pixel(x, y).0;
+        let a = a.clamp(0.0, 1.0);
+        if a == 0.0 {
+            return Rgba([0, 0, 0, 0]);
+        }
+        let channel = |c: f32| ((c / a).clamp(0.0, 1.0) * 255.0).round() as u8;
+        Rgba([
+            channel(r),
+            channel(g),
+            channel(b),
+            (a * 255.0).round() as u8,
+        ])
+    })
+}
+
+impl Animation {
+    /// Decodes a GIF, keeping each frame's patch, offset, delay and disposal.
+    pub fn decode_gif(bytes: &[u8]) -> Result<Self, Box<dyn Error>> {
+        let mut options = gif::DecodeOptions::new();
+        options.set_color_output(gif::ColorOutput::RGBA);
+        let mut decoder = options.read_info(bytes)?;
+        let mut animation = Animation {
+            width: decoder.width() as u32,
+            height: decoder.height() as u32,
+            frames: Vec::new(),
+        };
+        while let Some(frame) = decoder.read_next_frame()? {
+            let image = RgbaImage::from_raw(
+                frame.width as u32,
+                frame.height as u32,
+                frame.buffer.to_vec(),
+            )
+            .ok_or("frame is smaller than its size")?;
+            animation.frames.push(Frame {
+                image,
+                left: frame.left as u32,
+                top: frame.top as u32,
+                delay: frame.delay as u32 * 10,
+                dispose: frame.dispose,
+            });
+        }
+        Ok(animation)
+    }
+
+    /// Decodes an animated (or still) WebP into whole canvas frames.
+    pub fn decode_webp(bytes: &[u8]) -> Result<Self, Box<dyn Error>> {
+        let decoded = webp::AnimDecoder::new(bytes).decode()?;
+        let mut animation = Animation {
+            width: 0,
+            height: 0,
+            frames: Vec::new(),
+        };
+        // timestamps are when each frame ends
+        let mut start = 0;
+        for frame in &decoded {
+            animation.width = frame.width();
+            animation.height = frame.height();
+            let image =
+                RgbaImage::from_raw(frame.width(), frame.height(), frame.get_image().to_vec())
+                    .ok_or("frame is smaller than its size")?;
+            animation.frames.push(Frame {
+                image,
+                left: 0,
+                top: 0,
+                delay: (frame.get_time_ms() - start).max(0) as u32,
+                dispose: DisposalMethod::Background,
+            });
+            start = frame.get_time_ms();
+        }
+        if animation.frames.is_empty() {
+            return Err("WebP has no frames".into());
+        }
+        Ok(animation)
+    }
+
+    /// Decodes a GIF or a WebP, going by its first bytes.
+    pub fn decode(bytes: &[u8]) -> Result<Self, Box<dyn Error>> {
+        if bytes.starts_with(b"GIF8") {
+            Self::decode_gif(bytes)
+        } else if bytes.len() >= 12 && &bytes[..4] == b"RIFF" && &bytes[8..12] == b"WEBP" {
+            Self::decode_webp(bytes)
+        } else {
+            Err("not a GIF or WebP".into())
+        }
+    }
+
+    /// Scales the animation onto a `width` x `height` canvas, and each
+    /// frame's patch and offset along with it.
+    pub fn resize(&self, width: u32, height: u32, aspect: Aspect, filter: Filter) -> Animation {
+        let scale_x = width as f64 / self.width as f64;
+        let scale_y = height as f64 / self.height as f64;
+        let (scale_x, scale_y) = match aspect {
+            Aspect::Fit => (scale_x.min(scale_y), scale_x.min(scale_y)),
+            Aspect::Fill => (scale_x.max(scale_y), scale_x.max(scale_y)),
+            Aspect::Stretch => (scale_x, scale_y),
+        };
+        // centered, so negative when cropped
+        let offset_x = (width as f64 - self.width as f64 * scale_x) / 2.0;
+        let offset_y = (height as f64 - self.height as f64 * scale_y) / 2.0;
+
+        let frames = self
+            .frames
+            .iter()
+            .map(|frame| {
+                // scaling both edges (rather than the offset and size) keeps
+                // patches that touch in the original touching after rounding
+                let left = (frame.left as f64 * scale_x + offset_x).round() as i64;
+                let top = (frame.top as f64 * scale_y + offset_y).round() as i64;
+                let right =
+                    ((frame.left + frame.image.width()) as f64 * scale_x + offset_x).round() as i64;
+                let bottom =
+                    ((frame.top + frame.image.height()) as f64 * scale_y + offset_y).round() as i64;
+                let resized = resize_premultiplied(
+                    &frame.image,
+                    (right - left).max(1) as u32,
+                    (bottom - top).max(1) as u32,
+                    filter,
+                );
+
+                // then cropped to the canvas
+                let (clip_left, clip_top) = (left.max(0), top.max(0));
+                let clip_right = right.min(width as i64);
+                let clip_bottom = bottom.min(height as i64);
+                if clip_left >= clip_right || clip_top >= clip_bottom {
+                    // cropped away entirely, but its delay still counts
+                    return Frame {
+                        image: RgbaImage::new(1, 1),
+                        left: 0,
+                        top: 0,
+                        delay: frame.delay,
+                        dispose: DisposalMethod::Keep,
+                    };
+                }
+                let image = imageops::crop_imm(
+                    &resized,
+                    (clip_left - left) as u32,
+                    (clip_top - top) as u32,
+                    (clip_right - clip_left) as u32,
+                    (clip_bottom - clip_top) as u32,
+                )
+                .to_image();
+                Frame {
+                    image,
+                    left: clip_left as u32,
+                    top: clip_top as u32,
+                    ..frame.clone()
+                }
+            })
+            .collect();
+        Animation {
+            width,
+            height,
+            frames,
+        }
+    }
+
+    /// The animation as full canvas frames, each disposed before the next, so
+    /// frames can be dropped without losing what they drew.
+    pub fn composite(&self) -> Animation {
+        let mut canvas = RgbaImage::new(self.width, self.height);
+        let mut frames = Vec::new();
+        for frame in &self.frames {
+            let previous = (frame.dispose == DisposalMethod::Previous).then(|| canvas.clone());
+            // blended over what's below, which GIF encoding binarizes later
+            imageops::overlay(
+                &mut canvas,
+                &frame.image,
+                frame.left as i64,
+                frame.top as i64,
+            );
+            frames.push(Frame {
+                image: canvas.clone(),
+                left: 0,
+                top: 0,
+                delay: frame.delay,
+                dispose: DisposalMethod::Background,
+            });
+            match (frame.dispose, previous) {
+                (DisposalMethod::Previous, Some(previous)) => canvas = previous,
+                (DisposalMethod::Background, _) => {
+                    for y in frame.top..(frame.top + frame.image.height()).min(self.height) {
+                        for x in frame.left..(frame.left + frame.image.width()).min(self.width) {
+                            canvas.put_pixel(x, y, Rgba([0, 0, 0, 0]));
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        Animation {
+            width: self.width,
+            height: self.height,
+            frames,
+        }
+    }
+
+    /// Keeps every `keep_every`th frame, shown for as long as the frames
+    /// dropped after it were. Expects `composite` frames.
+    pub fn drop_frames(&self, keep_every: usize) -> Animation {
+        let frames = self
+            .frames
+            .chunks(keep_every.max(1))
+            .map(|frames| Frame {
+                delay: frames
+                    .iter()
+                    .fold(0u32, |delay, frame| delay.saturating_add(frame.delay)),
+                ..frames[0].clone()
+            })
+            .collect();
+        Animation {
+            width: self.width,
+            height: self.height,
+            frames,
+        }
+    }
+
+    pub fn retime(&mut self, retime: Retime) {
+        for frame in &mut self.frames {
+            let delay = match retime {
+                Retime::Fps(fps) => 1000.0 / fps,
+                Retime::Speed(speed) => frame.delay as f64 / speed,
+            };
+            // a delay of 0 is played back at an arbitrary, browser specific
+            // speed, so retimed frames get at least GIF's 10ms
+            frame.delay = (delay.round() as u32).max(10);
+        }
+    }
+
+    /// Encodes the animation as a GIF that loops forever, with at most
+    /// `colors` (up to 256) per frame.
+    pub fn encode_gif(&self, colors: usize) -> Result<Vec<u8>, Box<dyn Error>> {
+        let mut bytes = Vec::new();
+        {
+            let mut encoder =
+                gif::Encoder::new(&mut bytes, self.width as u16, self.height as u16, &[])?;
+            encoder.set_repeat(Repeat::Infinite)?;
+            for frame in &self.frames {
+                let mut pixels = frame.image.as_raw().clone();
+                // GIF transparency is all or nothing
+                for pixel in pixels.chunks_exact_mut(4) {
+                    if pixel[3] < 128 {
+                        pixel.copy_from_slice(&[0, 0, 0, 0]);
+                    } else {
+                        pixel[3] = 255;
+                    }
+                }
+                let (indexes, palette, transparent) =
+                    quantize(&pixels, colors.clamp(2, MAX_COLORS));
+                let mut gif_frame = gif::Frame::from_palette_pixels(
+                    frame.image.width() as u16,
+                    frame.image.height() as u16,
+                    &indexes,
+                    &palette,
+                    transparent,
+                );
+                gif_frame.left = frame.left as u16;
+                gif_frame.top = frame.top as u16;
+                gif_frame.delay = match frame.delay {
+                    0 => 0,
+                    delay => ((delay + 5) / 10).clamp(1, u16::MAX as u32) as u16,
+                };
+                gif_frame.dispose = frame.dispose;
+                encoder.write_frame(&gif_frame)?;
+            }
+        }
+        Ok(bytes)
+    }
+
+    /// Encodes the animation as a lossy WebP with full alpha that loops
+    /// forever.
+    pub fn encode_webp(&self) -> Result<Vec<u8>, Box<dyn Error>> {
+        let mut config = webp::WebPConfig::new().map_err(|_| "invalid WebP config")?;
+        config.quality = 90.0;
+        // the encoder takes whole canvases, each shown from its timestamp
+        let composited = self.composite();
+        let mut encoder = webp::AnimEncoder::new(self.width, self.height, &config);
+        encoder.set_loop_count(0);
+        let mut start = 0;
+        for frame in &composited.frames {
+            encoder.add_frame(webp::AnimFrame::from_rgba(
+                frame.image.as_raw(),
+                self.width,
+                self.height,
+                start as i32,
+            ));
+            start += frame.delay;
+        }
+        let mut bytes = encoder
+            .try_encode()
+            .map_err(|err| format!("WebP encoding failed: {:?}", err))?
+            .to_vec();
+
+        // the encoder can't be told when the last frame ends, so libwebp
+        // shows it for the average delay of the others instead
+        if let (Some(offset), Some(last)) = (last_frame_duration(&bytes), composited.frames.last())
+        {
+            let count = composited.frames.len() as u32;
+            let average = match count {
+                1 => 0,
+                _ => (start - last.delay) / (count - 1),
+            };
+            let field = &mut bytes[offset..offset + 3];
+            let duration = u32::from_le_bytes([field[0], field[1], field[2], 0]);
+            let duration = (duration + last.delay)
+                .saturating_sub(average)
+                .min(0xff_ffff);
+            field.copy_from_slice(&duration.to_le_bytes()[..3]);
+        }
+        Ok(bytes)
+    }
+
+    /// Encodes the animation as an APNG that loops forever.
+    pub fn encode_apng(&self) -> Result<Vec<u8>, Box<dyn Error>> {
+        // the first frame doubles as the still image, so must cover the canvas
+        let composited;
+        let animation = match self.frames.first() {
+            Some(first)
+                if first.left == 0
+                    && first.top == 0
+                    && first.image.dimensions() == (self.width, self.height) =>
+            {
+                self
+            }
+            _ => {
+                composited = self.composite();
+                &composited
+            }
+        };
+
+        let mut bytes = Vec::new();
+        {
+            let mut encoder = png::Encoder::new(&mut bytes, self.width, self.height);
+            encoder.set_color(png::ColorType::Rgba);
+            encoder.set_depth(png::BitDepth::Eight);
+            encoder.set_animated(animation.frames.len() as u32, 0)?;
+            let mut writer = encoder.write_header()?;
+            for frame in &animation.frames {
+                writer.reset_frame_position()?;
+                writer.set_frame_dimension(frame.image.width(), frame.image.height())?;
+                writer.set_frame_position(frame.left, frame.top)?;
+                writer.set_frame_delay(frame.delay.min(u16::MAX as u32) as u16, 1000)?;
+                writer.set_dispose_op(match frame.dispose {
+                    DisposalMethod::Background => png::DisposeOp::Background,
+                    DisposalMethod::Previous => png::DisposeOp::Previous,
+                    _ => png::DisposeOp::None,
+                })?;
+                writer.set_blend_op(png::BlendOp::Over)?;
+                writer.write_image_data(frame.image.as_raw())?;
+            }
+            writer.finish()?;
+        }
+        Ok(bytes)
+    }
+
+    /// Encodes the animation as `format`, with at most `colors` per frame
+    /// for GIFs.
+    pub fn encode(&self, format: Format, colors: usize) -> Result<Vec<u8>, Box<dyn Error>> {
+        match format {
+            Format::Gif => self.encode_gif(colors),
+            Format::Webp => self.encode_webp(),
+            Format::Apng => self.encode_apng(),
+            Format::LottieJson => Err("Lottie JSON can't be encoded from frames".into()),
+        }
+    }
+}
+
+/// Where the last frame's 24-bit duration is in an animated WebP.
+fn last_frame_duration(webp: &[u8]) -> Option<usize> {
+    // chunks after the RIFF header, each padded to an even size
+    let mut offset = 12;
+    let mut last = None;
+    while offset + 8 <= webp.len() {
+        let size = u32::from_le_bytes(webp[offset + 4..offset + 8].try_into().ok()?) as usize;
+        // after the frame's offset and size, 3 bytes each
+        if &webp[offset..offset + 4] == b"ANMF" && size >= 16 {
+            last = Some(offset + 8 + 12);
+        }
+        offset += 8 + size + size % 2;
+    }
+    last
+}
+
+/// Resizes (and optionally retimes) a GIF or WebP to `options.size` square,
+/// keeping its frame timing, disposal and offsets, and encodes it as
+/// `options.format`. With a budget, colors (for GIFs), frames and size are
+/// reduced in turn until it fits. Lottie JSON is returned as is.
+pub fn resize_animation(bytes: &[u8], options: &Options) -> Result<Encoded, Box<dyn Error>> {
+    if options.format == Format::LottieJson {
+        return Ok(Encoded {
+            bytes: bytes.to_vec(),
+            reduction: None,
+        });
+    }
+    let original = Animation::decode(bytes)?;
+    let prepare = |size: u32| {
+        let mut animation = original.resize(size, size, options.aspect, options.filter);
+        if let Some(retime) = options.retime {
+            animation.retime(retime);
+        }
+        animation
+    };
+
+    let budget = match options.budget {
+        None => {
+            return Ok(Encoded {
+                bytes: prepare(options.size).encode(options.format, MAX_COLORS)?,
+                reduction: None,
+            })
+        }
+        Some(budget) => budget,
+    };
+
+    let mut level = Level::new(options.format == Format::Gif);
+    let mut resized = (level.scale, prepare(options.size));
+    loop {
+        let size = ((options.size as f64 * level.scale).round() as u32).max(1);
+        if resized.0 != level.scale {
+            resized = (level.scale, prepare(size));
+        }
+        let dropped;
+        let animation = if level.keep_every > 1 {
+            dropped = resized.1.composite().drop_frames(level.keep_every);
+            &dropped
+        } else {
+            &resized.1
+        };
+        let bytes = animation.encode(options.format, level.colors)?;
+        let fits = bytes.len() as u64 <= budget;
+        if fits || !level.next() {
+            let reduction = Reduction {
+                frames: animation.frames.len(),
+                original_frames: original.frames.len(),
+                colors: level.colors,
+                size,
+                original_size: options.size,
+                bytes: bytes.len(),
+                fits,
+            };
+            return Ok(Encoded {
+                bytes,
+                reduction: Some(reduction),
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A 4x4 animation of whole canvas frames, each a different color.
+    fn animation(delays: &[u32]) -> Animation {
+        let frames = delays
+            .iter()
+            .enumerate()
+            .map(|(i, &delay)| Frame {
+                image: RgbaImage::from_pixel(4, 4, Rgba([(i as u8 + 1) * 40, 0, 0, 255])),
+                left: 0,
+                top: 0,
+                delay,
+                dispose: DisposalMethod::Background,
+            })
+            .collect();
+        Animation {
+            width: 4,
+            height: 4,
+            frames,
+        }
+    }
+
+    fn delays(animation: &Animation) -> Vec<u32> {
+        animation.frames.iter().map(|frame| frame.delay).collect()
+    }
+
+    /// An 8x4 single frame animation whose red channel is the column.
+    fn wide() -> Animation {
+        Animation {
+            width: 8,
+            height: 4,
+            frames: vec![Frame {
+                image: RgbaImage::from_fn(8, 4, |x, _| Rgba([x as u8 * 30, 0, 0, 255])),
+                left: 0,
+                top: 0,
+                delay: 100,
+                dispose: DisposalMethod::Background,
+            }],
+        }
+    }
+
+    #[test]
+    fn fit_centers_the_frame() {
+        let resized = wide().resize(4, 4, Aspect::Fit, Filter::Nearest);
+        let frame = &resized.frames[0];
+        assert_eq!((resized.width, resized.height), (4, 4));
+        assert_eq!((frame.left, frame.top), (0, 1));
+        assert_eq!(frame.image.dimensions(), (4, 2));
+    }
+
+    #[test]
+    fn fill_crops_the_frame() {
+        let resized = wide().resize(4, 4, Aspect::Fill, Filter::Nearest);
+        let frame = &resized.frames[0];
+        assert_eq!((frame.left, frame.top), (0, 0));
+        assert_eq!(frame.image.dimensions(), (4, 4));
+        // the middle columns, 2 to 5
+        let columns: Vec<u8> = (0..4).map(|x| frame.image.get_pixel(x, 0)[0]).collect();
+        assert_eq!(columns, [60, 90, 120, 150]);
+    }
+
+    #[test]
+    fn half_transparent_edges_keep_their_color() {
+        // white, with a half transparent edge next to transparent black
+        let image = RgbaImage::from_fn(4, 1, |x, _| match x {
+            0 => Rgba([255, 255, 255, 255]),
+            1 => Rgba([255, 255, 255, 128]),
+            _ => Rgba([0, 0, 0, 0]),
+        });
+        let resized = resize_premultiplied(&image, 8, 1, Filter::Triangle);
+        for pixel in resized.pixels().filter(|pixel| pixel[3] > 0) {
+            assert!(pixel.0[..3].iter().all(|&c| c >= 254), "{:?}", pixel);
+        }
+        // and the edge is still there
+        assert!(resized.pixels().any(|pixel| pixel[3] > 0 && pixel[3] < 255));
+    }
+
+    #[test]
+    fn gif_keeps_frame_delays() {
+        let bytes = animation(&[100, 200, 300]).encode_gif(MAX_COLORS).unwrap();
+        let decoded = Animation::decode(&bytes).unwrap();
+        assert_eq!((decoded.width, decoded.height), (4, 4));
+        assert_eq!(delays(&decoded), [100, 200, 300]);
+    }
+
+    #[test]
+    fn webp_keeps_frame_delays_including_the_last() {
+        let bytes = animation(&[100, 200, 300]).encode_webp().unwrap();
+        let offset = last_frame_duration(&bytes).unwrap();
+        let field = &bytes[offset..offset + 3];
+        assert_eq!(u32::from_le_bytes([field[0], field[1], field[2], 0]), 300);
+
+        let decoded = Animation::decode(&bytes).unwrap();
+        assert_eq!((decoded.width, decoded.height), (4, 4));
+        assert_eq!(delays(&decoded), [100, 200, 300]);
+    }
+
+    #[test]
+    fn resizing_a_gif_to_webp_keeps_frame_delays() {
+        let gif = animation(&[100, 200, 300]).encode_gif(MAX_COLORS).unwrap();
+        let options = Options {
+            format: Format::Webp,
+            size: 8,
+            filter: Filter::default(),
+            aspect: Aspect::default(),
+            retime: None,
+            budget: None,
+        };
+        let encoded = resize_animation(&gif, &options).unwrap();
+        let decoded = Animation::decode(&encoded.bytes).unwrap();
+        assert_eq!((decoded.width, decoded.height), (8, 8));
+        assert_eq!(delays(&decoded), [100, 200, 300]);
+    }
+
+    fn budgeted(budget: u64) -> Options {
+        Options {
+            format: Format::Gif,
+            size: 16,
+            filter: Filter::default(),
+            aspect: Aspect::default(),
+            retime: None,
+            budget: Some(budget),
+        }
+    }
+
+    #[test]
+    fn levels_take_turns_until_fully_reduced() {
+        let mut level = Level::new(true);
+        assert!(level.next());
+        assert_eq!((level.colors, level.keep_every, level.scale), (128, 1, 1.0));
+        assert!(level.next());
+        assert_eq!((level.colors, level.keep_every, level.scale), (128, 2, 1.0));
+        assert!(level.next());
+        assert_eq!(
+            (level.colors, level.keep_every, level.scale),
+            (128, 2, 0.875)
+        );
+        while level.next() {}
+        assert_eq!((level.colors, level.keep_every, level.scale), (16, 4, 0.5));
+
+        // without a palette, colors are skipped
+        let mut level = Level::new(false);
+        assert!(level.next());
+        assert_eq!((level.colors, level.keep_every), (MAX_COLORS, 2));
+    }
+
+    #[test]
+    fn reduces_until_the_budget_fits() {
+        let gif = animation(&[100; 6]).encode_gif(MAX_COLORS).unwrap();
+        let unbudgeted = resize_animation(&gif, &budgeted(u64::MAX)).unwrap();
+        let reduction = unbudgeted.reduction.unwrap();
+        assert!(reduction.fits && reduction.is_lossless());
+
+        let budget = unbudgeted.bytes.len() as u64 - 1;
+        let encoded = resize_animation(&gif, &budgeted(budget)).unwrap();
+        let reduction = encoded.reduction.unwrap();
+        assert!(reduction.fits);
+        assert!(!reduction.is_lossless());
+        assert!(encoded.bytes.len() as u64 <= budget);
+        assert_eq!(reduction.bytes, encoded.bytes.len());
+    }
+
+    #[test]
+    fn returns_the_last_level_when_the_budget_is_out_of_reach() {
+        let gif = animation(&[100; 6]).encode_gif(MAX_COLORS).unwrap();
+        let encoded = resize_animation(&gif, &budgeted(1)).unwrap();
+        let reduction = encoded.reduction.unwrap();
+        assert!(!reduction.fits);
+        assert_eq!(
+            (reduction.frames, reduction.colors, reduction.size),
+            (2, 16, 8)
+        );
+        assert_eq!(reduction.original_frames, 6);
+        assert_eq!(reduction.original_size, 16);
+        assert_eq!(Animation::decode(&encoded.bytes).unwrap().frames.len(), 2);
+    }
+
+    #[test]
+    fn dropped_frames_delays_go_to_the_kept_frame() {
+        let dropped = animation(&[10, 20, 30, 40, 50]).drop_frames(2);
+        assert_eq!(delays(&dropped), [30, 70, 50]);
+        // the kept frames are the first of each pair
+        assert_eq!(dropped.frames[1].image.get_pixel(0, 0)[0], 120);
+        assert_eq!(delays(&dropped).iter().sum::<u32>(), 150);
+    }
+
+    #[test]
+    fn still_webp_has_no_frame_duration() {
+        let image = RgbaImage::from_pixel(4, 4, Rgba([255, 0, 0, 255]));
+        let bytes = webp::Encoder::from_rgba(image.as_raw(), 4, 4).encode(90.0);
+        assert_eq!(last_frame_duration(&bytes), None);
+        assert_eq!(Animation::decode(&bytes).unwrap().frames.len(), 1);
+    }
+}
@@ -1,12 +1,13 @@
 use std::error::Error;
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
 
+use emoji_kitchen::animation;
+use emoji_kitchen::utils;
 use futures::StreamExt;
 use serde::Deserialize;
 
-use crate::utils;
-
 #[derive(Deserialize, Debug)]
 struct AnimatedEmoji {
     // name: String,
@@ -16,8 +17,6 @@ struct AnimatedEmoji {
 }
 
 impl AnimatedEmoji {
-    const IMAGE_FORMAT: &str = "gif";
-
     fn short_name(&self) -> String {
         match self.tags.first() {
             None => self.codepoint.to_string(),
@@ -30,20 +29,26 @@ impl AnimatedEmoji {
         }
     }
 
-    fn filename(&self) -> String {
+    fn filename(&self, format: animation::Format) -> String {
         format!(
             "{}.{}.{}",
             self.short_name(),
             self.codepoint,
-            AnimatedEmoji::IMAGE_FORMAT
+            format.extension()
         )
     }
 
-    fn image_url(&self) -> String {
+    /// The source `format` is made from: the WebP for anything with full
+    /// alpha, since the GIF only has 1-bit transparency.
+    fn image_url(&self, format: animation::Format) -> String {
+        let source = match format {
+            animation::Format::Gif => "512.gif",
+            animation::Format::Webp | animation::Format::Apng => "512.webp",
+            animation::Format::LottieJson => "lottie.json",
+        };
         format!(
-            "https://fonts.gstatic.com/s/e/notoemoji/latest/{}/512.{}",
-            self.codepoint,
-            AnimatedEmoji::IMAGE_FORMAT
+            "https://fonts.gstatic.com/s/e/notoemoji/latest/{}/{}",
+            self.codepoint, source
         )
     }
 }
@@ -54,22 +59,111 @@ struct AnimationsBody {
     emoji: Vec<AnimatedEmoji>,
 }
 
-async fn fetch_animations() -> Result<AnimationsBody, Box<dyn Error>> {
-    let res =
-        reqwest::get("https://googlefonts.github.io/noto-emoji-animation/data/api.json").await?;
+/// Where the list of animations (`api.json`) comes from.
+pub struct Catalog {
+    /// a local snapshot to read instead of the cache or network
+    pub file: Option<PathBuf>,
+    /// only use the cached copy, however old
+    pub offline: bool,
+    /// how long the cached copy is used before fetching it again
+    pub ttl: Duration,
+}
+
+impl Catalog {
+    const URL: &str = "https://googlefonts.github.io/noto-emoji-animation/data/api.json";
+
+    fn cache_path() -> Option<PathBuf> {
+        dirs::cache_dir().map(|dir| {
+            dir.join(env!("CARGO_PKG_NAME"))
+                .join("noto-animations.json")
+        })
+    }
+
+    fn read(path: &Path) -> Result<AnimationsBody, Box<dyn Error>> {
+        let bytes =
+            fs::read(path).map_err(|err| format!("could not read {}: {}", path.display(), err))?;
+        serde_json::from_slice(&bytes)
+            .map_err(|err| format!("could not parse {}: {}", path.display(), err).into())
+    }
+
+    fn is_fresh(&self, path: &Path) -> bool {
+        fs::metadata(path)
+            .and_then(|metadata| metadata.modified())
+            .ok()
+            .and_then(|modified| SystemTime::now().duration_since(modified).ok())
+            .is_some_and(|age| age < self.ttl)
+    }
+
+    /// The snapshot if given, then the cached copy if it's fresh (or
+    /// `offline`), then the network. A stale cached copy is still used when
+    /// the fetch fails; one that doesn't parse is fetched again.
+    async fn load(&self) -> Result<AnimationsBody, Box<dyn Error>> {
+        if let Some(file) = &self.file {
+            return Catalog::read(file);
+        }
+
+        let cache = Catalog::cache_path().filter(|path| path.is_file());
+        match &cache {
+            Some(path) if self.offline => return Catalog::read(path),
+            Some(path) if self.is_fresh(path) => match Catalog::read(path) {
+                Ok(body) => return Ok(body),
+                Err(err) => println!("⚠️ {}, fetching it again", err),
+            },
+            None if self.offline => {
+                return Err("no cached catalog; run once online or pass --catalog-file".into())
+            }
+            _ => {}
+        }
+
+        match Catalog::fetch().await {
+            Ok(bytes) => {
+                let body = serde_json::from_slice(&bytes)?;
+                if let Err(err) = Catalog::save(&bytes) {
+                    println!("⚠️ could not cache the catalog: {}", err);
+                }
+                Ok(body)
+            }
+            Err(err) => match &cache {
+                Some(path) => {
+                    println!("⚠️ using the cached catalog, fetching it failed: {}", err);
+                    Catalog::read(path)
+                }
+                None => Err(err),
+            },
+        }
+    }
+
+    async fn fetch() -> Result<Vec<u8>, Box<dyn Error>> {
+        let res = reqwest::get(Catalog::URL).await?;
 
-    match res.status() {
-        status if status.is_success() => {
-            let body = res.json::<AnimationsBody>().await?;
-            Ok(body)
+        match res.status() {
+            status if status.is_success() => Ok(res.bytes().await?.to_vec()),
+            status => Err(Box::<dyn Error>::from(status.to_string())),
         }
-        status => Err(Box::<dyn Error>::from(status.to_string())),
+    }
+
+    /// Writes to a temporary file first, so an interrupted save can't leave
+    /// a truncated cache behind.
+    fn save(bytes: &[u8]) -> Result<(), Box<dyn Error>> {
+        let path = Catalog::cache_path().ok_or("no cache dir")?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let tmp_path = path.with_extension("json.tmp");
+        fs::write(&tmp_path, bytes)?;
+        fs::rename(&tmp_path, &path)?;
+        Ok(())
     }
 }
 
-pub async fn animations(output_path: &Path, name: &Option<String>, size: &u32) {
-    match fetch_animations().await {
-        Err(err) => println!("Failed to fetch animations: {}", err),
+pub async fn animations(
+    output_path: &Path,
+    name: &Option<String>,
+    catalog: &Catalog,
+    options: animation::Options,
+) {
+    match catalog.load().await {
+        Err(err) => println!("🚫 Failed to load animations: {}", err),
         Ok(animations) => {
             let emoji = match name {
                 None => animations.emoji,
@@ -79,27 +173,38 @@ pub async fn animations(output_path: &Path, name: &Option<String>, size: &u32) {
                     .filter(|emoji| emoji.short_name().eq(name))
                     .collect(),
             };
-            download(emoji, output_path, size).await;
+            download(emoji, output_path, &options).await;
         }
     }
 }
 
-async fn download(animated_emoji: Vec<AnimatedEmoji>, output_path: &Path, size: &u32) {
-    fs::create_dir_all(&output_path).expect(&format!(
-        "could not create output dir: {}",
-        output_path.display()
-    ));
+async fn download(
+    animated_emoji: Vec<AnimatedEmoji>,
+    output_path: &Path,
+    options: &animation::Options,
+) {
+    fs::create_dir_all(output_path)
+        .unwrap_or_else(|_| panic!("could not create output dir: {}", output_path.display()));
 
+    let client = &utils::retrying_client();
     let download_iter = animated_emoji.into_iter().map(|emoji| async move {
-        let image_url = emoji.image_url();
-        let filename = emoji.filename();
+        let image_url = emoji.image_url(options.format);
+        let filename = emoji.filename(options.format);
         let dest_path = &output_path.join(&filename);
 
-        match utils::download_and_resize_animation(&image_url, &dest_path, size).await {
+        match utils::download_and_resize_animation(client, &image_url, dest_path, options).await {
             Err(err) => {
                 println!("🚫 {} {} {}", filename, image_url, err);
                 Ok(Some((format!("{} {}", filename, image_url), err)))
             }
+            Ok(Some(reduction)) if !reduction.fits => {
+                println!("⚠️ {} is over budget even at {}", filename, reduction);
+                Ok(None)
+            }
+            Ok(Some(reduction)) if !reduction.is_lossless() => {
+                println!("✅ {} {} ({})", filename, image_url, reduction);
+                Ok(None)
+            }
             Ok(_) => {
                 println!("✅ {} {}", filename, image_url);
                 Ok(None)
@@ -113,12 +218,8 @@ async fn download(animated_emoji: Vec<AnimatedEmoji>, output_path: &Path, size:
 
     let errors = downloads.await;
 
-    for error in errors {
-        if let Ok(result) = error {
-            if let Some((message, err)) = result {
-                println!("🚫 {} {}", message, err);
-            }
-        }
+    for (message, err) in errors.into_iter().flatten().flatten() {
+        println!("🚫 {} {}", message, err);
     }
 
     // let mut errors: Vec<AnError> = Vec::new();
@@ -0,0 +1,125 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use image::{imageops, DynamicImage, RgbaImage};
+use serde::Serialize;
+
+/// Where a sprite is in the atlas.
+#[derive(Serialize, Clone, Debug)]
+pub struct Sprite {
+    /// index into `Atlas.sheets`
+    pub sheet: usize,
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Sprite sheets of equally sized cells, with each sprite keyed by
+/// `EmojiPair.name`.
+#[derive(Serialize, Debug)]
+pub struct Atlas {
+    pub cell_size: u32,
+    /// cells per row of a full sheet
+    pub columns: u32,
+    /// filenames of the sheets, relative to the atlas
+    pub sheets: Vec<String>,
+    pub sprites: BTreeMap<String, Sprite>,
+}
+
+impl Atlas {
+    /// An empty atlas whose sheets are at most `max_size` pixels wide and high.
+    pub fn new(cell_size: u32, max_size: u32) -> Self {
+        Atlas {
+            cell_size,
+            columns: (max_size / cell_size).max(1),
+            sheets: Vec::new(),
+            sprites: BTreeMap::new(),
+        }
+    }
+
+    /// How many cells fit on a sheet.
+    pub fn cells_per_sheet(&self) -> usize {
+        (self.columns * self.columns) as usize
+    }
+
+    /// `image` scaled to fit a cell, centered on a transparent background.
+    pub fn cell(&self, image: &DynamicImage) -> RgbaImage {
+        let size = self.cell_size;
+        let scaled = image
+            .resize(size, size, imageops::FilterType::Lanczos3)
+            .to_rgba8();
+        let mut cell = RgbaImage::new(size, size);
+        let left = (size - scaled.width()) / 2;
+        let top = (size - scaled.height()) / 2;
+        imageops::overlay(&mut cell, &scaled, left as i64, top as i64);
+        cell
+    }
+
+    /// Packs `cells` (at most `cells_per_sheet`) into a new sheet named
+    /// `filename`, row by row, and records where each one went.
+    pub fn add_sheet(&mut self, filename: &str, cells: &[(String, RgbaImage)]) -> RgbaImage {
+        let count = cells.len().min(self.cells_per_sheet()) as u32;
+        let columns = self.columns.min(count).max(1);
+        let rows = count.div_ceil(columns).max(1);
+        let mut sheet = RgbaImage::new(columns * self.cell_size, rows * self.cell_size);
+        let index = self.sheets.len();
+
+        for (i, (name, cell)) in cells.iter().take(count as usize).enumerate() {
+            let x = (i as u32 % columns) * self.cell_size;
+            let y = (i as u32 / columns) * self.cell_size;
+            imageops::replace(&mut sheet, cell, x as i64, y as i64);
+            self.sprites.insert(
+                name.to_owned(),
+                Sprite {
+                    sheet: index,
+                    x,
+                    y,
+                    width: self.cell_size,
+                    height: self.cell_size,
+                },
+            );
+        }
+
+        self.sheets.push(filename.to_owned());
+        sheet
+    }
+
+    /// A `.<prefix>` class sizing each sprite, plus a `.<prefix>-<name>`
+    /// class per sprite that selects it, e.g.
+    /// `<i class="ek ek-cat_pizza"></i>`.
+    pub fn css(&self, prefix: &str) -> String {
+        let prefix = css_escape(prefix);
+        let mut css = String::new();
+        let _ = writeln!(
+            css,
+            ".{} {{\n  display: inline-block;\n  width: {}px;\n  height: {}px;\n  background-repeat: no-repeat;\n}}",
+            prefix, self.cell_size, self.cell_size
+        );
+        for (name, sprite) in &self.sprites {
+            let _ = writeln!(
+                css,
+                ".{}-{} {{ background-image: url(\"{}\"); background-position: {}px {}px; }}",
+                prefix,
+                css_escape(name),
+                self.sheets[sprite.sheet],
+                -(sprite.x as i64),
+                -(sprite.y as i64)
+            );
+        }
+        css
+    }
+}
+
+/// Escapes the characters of a name that can't appear in a CSS class name,
+/// e.g. `+1` -> `\+1`.
+fn css_escape(name: &str) -> String {
+    let mut escaped = String::new();
+    for c in name.chars() {
+        if !(c.is_ascii_alphanumeric() || c == '_' || c == '-') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
@@ -0,0 +1,569 @@
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::error::Error;
+use std::io::{self, Write};
+use std::path::PathBuf;
+
+use crossterm::cursor::Show;
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyModifiers};
+use crossterm::execute;
+use crossterm::terminal::{
+    disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen,
+};
+use emoji_kitchen::emoji::{Emoji, EmojiDB, EmojiPair};
+use emoji_kitchen::manifest::Manifest;
+use emoji_kitchen::utils::{self, Fetched};
+use fuzzy_matcher::skim::SkimMatcherV2;
+use fuzzy_matcher::FuzzyMatcher;
+use reqwest_middleware::ClientWithMiddleware;
+use tui::backend::{Backend, CrosstermBackend};
+use tui::layout::{Constraint, Direction, Layout, Rect};
+use tui::style::{Color, Modifier, Style};
+use tui::widgets::{Block, Borders, List, ListItem, ListState, Paragraph};
+use tui::{Frame, Terminal};
+
+// a grid cell is the image plus a line for its name
+const IMAGE_WIDTH: u16 = 16;
+const IMAGE_HEIGHT: u16 = 8;
+const CELL_WIDTH: u16 = IMAGE_WIDTH + 2;
+const CELL_HEIGHT: u16 = IMAGE_HEIGHT + 2;
+const LIST_WIDTH: u16 = 32;
+const PAGE: usize = 10;
+
+const HELP: &str = "/ search  tab grid  c copy filename  d download  u queue for upload  q quit";
+
+pub struct Options {
+    /// where downloaded images are, and where `d` saves them
+    pub input: PathBuf,
+    /// where `u` saves images, named after the pair for `upload --pairs`
+    pub queue: PathBuf,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Focus {
+    List,
+    Grid,
+    Search,
+}
+
+struct App<'a> {
+    options: Options,
+    /// every emoji with at least one pair, by sort order, with its pair count
+    emoji: Vec<(&'a Emoji, usize)>,
+    /// indexes into `emoji` that match `search`, best match first
+    filtered: Vec<usize>,
+    list_state: ListState,
+    search: String,
+    focus: Focus,
+    /// the pairs of the selected emoji
+    combos: Vec<&'a EmojiPair>,
+    selected: usize,
+    /// the first visible row of the grid
+    scroll: usize,
+    /// the grid's inner area and size in cells, as of the last draw
+    grid: (Rect, usize, usize),
+    manifest: Manifest,
+    /// image url -> filename in `options.input`, from the manifest
+    files: HashMap<String, String>,
+    client: ClientWithMiddleware,
+    status: String,
+    /// the visible images changed and have to be printed again
+    stale: bool,
+    quit: bool,
+}
+
+impl<'a> App<'a> {
+    fn new(db: &'a EmojiDB, options: Options) -> Self {
+        let mut counts: HashMap<&str, usize> = HashMap::new();
+        for pair in db.pairs() {
+            *counts.entry(&pair.base.codepoint).or_default() += 1;
+            if pair.pair.codepoint != pair.base.codepoint {
+                *counts.entry(&pair.pair.codepoint).or_default() += 1;
+            }
+        }
+        let mut emoji: Vec<(&Emoji, usize)> = counts
+            .into_iter()
+            .map(|(codepoint, count)| (&db.emoji_map[codepoint], count))
+            .collect();
+        emoji.sort_by_key(|(emoji, _)| (emoji.sort_order, emoji.codepoint.to_owned()));
+
+        // a missing or unreadable manifest just means default filenames
+        let manifest = Manifest::load(&options.input).unwrap_or_default();
+        let files = manifest.filenames_by_url();
+
+        let mut app = App {
+            options,
+            emoji,
+            filtered: Vec::new(),
+            list_state: ListState::default(),
+            search: String::new(),
+            focus: Focus::List,
+            combos: Vec::new(),
+            selected: 0,
+            scroll: 0,
+            grid: (Rect::default(), 1, 1),
+            manifest,
+            files,
+            client: utils::retrying_client(),
+            status: String::new(),
+            stale: true,
+            quit: false,
+        };
+        app.filter(db);
+        app
+    }
+
+    /// Narrows the list to emoji whose names fuzzy match `search`.
+    fn filter(&mut self, db: &'a EmojiDB) {
+        let matcher = SkimMatcherV2::default();
+        let mut scored: Vec<(i64, usize)> = self
+            .emoji
+            .iter()
+            .enumerate()
+            .filter_map(|(i, (emoji, _))| {
+                if self.search.is_empty() {
+                    return Some((0, i));
+                }
+                emoji
+                    .names()
+                    .filter_map(|name| matcher.fuzzy_match(name, &self.search))
+                    .max()
+                    .map(|score| (score, i))
+            })
+            .collect();
+        scored.sort_by_key(|(score, i)| (Reverse(*score), *i));
+        self.filtered = scored.into_iter().map(|(_, i)| i).collect();
+        self.list_state.select(if self.filtered.is_empty() {
+            None
+        } else {
+            Some(0)
+        });
+        self.select_emoji(db);
+    }
+
+    fn current_emoji(&self) -> Option<&'a Emoji> {
+        let i = self.list_state.selected()?;
+        Some(self.emoji[self.filtered[i]].0)
+    }
+
+    fn select_emoji(&mut self, db: &'a EmojiDB) {
+        self.combos = match self.current_emoji() {
+            Some(emoji) => db.pairs_with(emoji).collect(),
+            None => Vec::new(),
+        };
+        self.selected = 0;
+        self.scroll = 0;
+        self.stale = true;
+    }
+
+    fn move_list(&mut self, db: &'a EmojiDB, delta: isize) {
+        let Some(current) = self.list_state.selected() else {
+            return;
+        };
+        let last = self.filtered.len() - 1;
+        let next = current.saturating_add_signed(delta).min(last);
+        if next != current {
+            self.list_state.select(Some(next));
+            self.select_emoji(db);
+        }
+    }
+
+    fn move_grid(&mut self, delta: isize) {
+        if self.combos.is_empty() {
+            return;
+        }
+        self.selected = self
+            .selected
+            .saturating_add_signed(delta)
+            .min(self.combos.len() - 1);
+        let (_, cols, rows) = self.grid;
+        let row = self.selected / cols;
+        let scroll = if row < self.scroll {
+            row
+        } else if row >= self.scroll + rows {
+            row + 1 - rows
+        } else {
+            self.scroll
+        };
+        if scroll != self.scroll {
+            self.scroll = scroll;
+            self.stale = true;
+        }
+    }
+
+    fn current_pair(&self) -> Option<&'a EmojiPair> {
+        self.combos.get(self.selected).copied()
+    }
+
+    /// The pair's filename in the input dir: whatever name `download` saved it
+    /// under, or the default one.
+    fn filename(&self, pair: &EmojiPair) -> String {
+        match self.files.get(&pair.image_url) {
+            Some(filename) => filename.to_owned(),
+            None => pair.filename.to_owned(),
+        }
+    }
+
+    fn image_path(&self, pair: &EmojiPair) -> Option<PathBuf> {
+        let path = self.options.input.join(self.filename(pair));
+        path.is_file().then_some(path)
+    }
+
+    /// Puts the selected pair's filename on the clipboard with an OSC 52
+    /// escape sequence, which also works over ssh.
+    fn copy_filename(&mut self) -> Result<(), Box<dyn Error>> {
+        let Some(pair) = self.current_pair() else {
+            return Ok(());
+        };
+        let filename = self.filename(pair);
+        let mut stdout = io::stdout();
+        write!(stdout, "\x1b]52;c;{}\x07", base64::encode(&filename))?;
+        stdout.flush()?;
+        self.status = format!("✅ copied {}", filename);
+        Ok(())
+    }
+
+    async fn download(&mut self) -> Result<(), Box<dyn Error>> {
+        let Some(pair) = self.current_pair() else {
+            return Ok(());
+        };
+        let filename = self.filename(pair);
+        let dest_path = self.options.input.join(&filename);
+        if let Some(parent) = dest_path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let fetched =
+            utils::download_and_save_image(&self.client, &pair.image_url, &dest_path, None).await?;
+        if let Fetched::Saved(entry) = fetched {
+            self.files
+                .insert(pair.image_url.to_owned(), filename.to_owned());
+            self.manifest.files.insert(filename, entry);
+            self.manifest.save(&self.options.input)?;
+        }
+        self.status = format!("✅ {}", dest_path.display());
+        self.stale = true;
+        Ok(())
+    }
+
+    async fn queue(&mut self) -> Result<(), Box<dyn Error>> {
+        let Some(pair) = self.current_pair() else {
+            return Ok(());
+        };
+        std::fs::create_dir_all(&self.options.queue)?;
+        let dest_path = self.options.queue.join(format!("{}.png", pair.name));
+        match self.image_path(pair) {
+            Some(path) => {
+                std::fs::copy(path, &dest_path)?;
+            }
+            None => {
+                utils::download_and_save_image(&self.client, &pair.image_url, &dest_path, None)
+                    .await?;
+            }
+        }
+        let queued = std::fs::read_dir(&self.options.queue)?.count();
+        self.status = format!(
+            "✅ queued {} ({} in {})",
+            pair.name,
+            queued,
+            self.options.queue.display()
+        );
+        Ok(())
+    }
+
+    async fn on_key(&mut self, db: &'a EmojiDB, key: KeyEvent) {
+        self.status.clear();
+
+        if key.modifiers.contains(KeyModifiers::CONTROL) && key.code == KeyCode::Char('c') {
+            self.quit = true;
+            return;
+        }
+
+        if self.focus == Focus::Search {
+            match key.code {
+                KeyCode::Char(c) => {
+                    self.search.push(c);
+                    self.filter(db);
+                }
+                KeyCode::Backspace => {
+                    self.search.pop();
+                    self.filter(db);
+                }
+                KeyCode::Esc => {
+                    self.search.clear();
+                    self.filter(db);
+                    self.focus = Focus::List;
+                }
+                KeyCode::Enter | KeyCode::Down | KeyCode::Tab => self.focus = Focus::List,
+                _ => {}
+            }
+            return;
+        }
+
+        let result = match key.code {
+            KeyCode::Char('q') => {
+                self.quit = true;
+                Ok(())
+            }
+            KeyCode::Char('/') => {
+                self.focus = Focus::Search;
+                Ok(())
+            }
+            KeyCode::Char('c') | KeyCode::Char('y') => self.copy_filename(),
+            KeyCode::Char('d') => self.download().await,
+            KeyCode::Char('u') => self.queue().await,
+            _ => Ok(()),
+        };
+        if let Err(err) = result {
+            self.status = format!("🚫 {}", err);
+        }
+
+        let cols = self.grid.1 as isize;
+        match (self.focus, key.code) {
+            (Focus::List, KeyCode::Esc) => self.quit = true,
+            (Focus::List, KeyCode::Up | KeyCode::Char('k')) => self.move_list(db, -1),
+            (Focus::List, KeyCode::Down | KeyCode::Char('j')) => self.move_list(db, 1),
+            (Focus::List, KeyCode::PageUp) => self.move_list(db, -(PAGE as isize)),
+            (Focus::List, KeyCode::PageDown) => self.move_list(db, PAGE as isize),
+            (Focus::List, KeyCode::Home | KeyCode::Char('g')) => self.move_list(db, isize::MIN),
+            (Focus::List, KeyCode::End | KeyCode::Char('G')) => self.move_list(db, isize::MAX),
+            (Focus::List, KeyCode::Enter | KeyCode::Tab | KeyCode::Right | KeyCode::Char('l'))
+                if !self.combos.is_empty() =>
+            {
+                self.focus = Focus::Grid
+            }
+            (Focus::Grid, KeyCode::Esc | KeyCode::Tab | KeyCode::BackTab) => {
+                self.focus = Focus::List
+            }
+            (Focus::Grid, KeyCode::Left | KeyCode::Char('h')) => self.move_grid(-1),
+            (Focus::Grid, KeyCode::Right | KeyCode::Char('l')) => self.move_grid(1),
+            (Focus::Grid, KeyCode::Up | KeyCode::Char('k')) => self.move_grid(-cols),
+            (Focus::Grid, KeyCode::Down | KeyCode::Char('j')) => self.move_grid(cols),
+            (Focus::Grid, KeyCode::PageUp) => self.move_grid(-cols * self.grid.2 as isize),
+            (Focus::Grid, KeyCode::PageDown) => self.move_grid(cols * self.grid.2 as isize),
+            _ => {}
+        }
+    }
+}
+
+fn ui<B: Backend>(f: &mut Frame<B>, app: &mut App) {
+    let columns = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Length(LIST_WIDTH), Constraint::Min(0)])
+        .split(f.size());
+    let left = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(0), Constraint::Length(3)])
+        .split(columns[0]);
+    let right = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(0), Constraint::Length(1)])
+        .split(columns[1]);
+
+    let highlight = |focused: bool| {
+        let style = Style::default().add_modifier(Modifier::BOLD);
+        if focused {
+            style.add_modifier(Modifier::REVERSED)
+        } else {
+            style
+        }
+    };
+
+    let items: Vec<ListItem> = app
+        .filtered
+        .iter()
+        .map(|i| {
+            let (emoji, count) = app.emoji[*i];
+            ListItem::new(format!("{} ({})", emoji.short_name, count))
+        })
+        .collect();
+    let list = List::new(items)
+        .block(Block::default().borders(Borders::ALL).title("emoji"))
+        .highlight_style(highlight(app.focus == Focus::List));
+    f.render_stateful_widget(list, left[0], &mut app.list_state);
+
+    let search_style = if app.focus == Focus::Search {
+        Style::default().fg(Color::Yellow)
+    } else {
+        Style::default()
+    };
+    let search = Paragraph::new(app.search.as_str())
+        .style(search_style)
+        .block(Block::default().borders(Borders::ALL).title("search"));
+    f.render_widget(search, left[1]);
+
+    let title = match app.current_emoji() {
+        Some(emoji) => format!("{} ({} pairs)", emoji.short_name, app.combos.len()),
+        None => "no matches".to_owned(),
+    };
+    let block = Block::default().borders(Borders::ALL).title(title);
+    let inner = block.inner(right[0]);
+    f.render_widget(block, right[0]);
+
+    let cols = (inner.width / CELL_WIDTH).max(1) as usize;
+    let rows = (inner.height / CELL_HEIGHT).max(1) as usize;
+    if (cols, rows) != (app.grid.1, app.grid.2) || inner != app.grid.0 {
+        app.stale = true;
+    }
+    app.grid = (inner, cols, rows);
+
+    for (i, pair) in visible(app) {
+        let Some(cell) = cell_rect(app, i) else {
+            continue;
+        };
+        if app.image_path(pair).is_none() {
+            let placeholder =
+                Paragraph::new("not downloaded").style(Style::default().fg(Color::DarkGray));
+            f.render_widget(placeholder, Rect { height: 1, ..cell });
+        }
+        let style = if i == app.selected {
+            highlight(app.focus == Focus::Grid)
+        } else {
+            Style::default()
+        };
+        let label_rect = Rect {
+            y: cell.y + IMAGE_HEIGHT,
+            height: 1,
+            ..cell
+        };
+        if let Some(label_rect) = clip(label_rect, cell) {
+            let label = Paragraph::new(pair.name.as_str()).style(style);
+            f.render_widget(label, label_rect);
+        }
+    }
+
+    let status = if app.status.is_empty() {
+        HELP
+    } else {
+        app.status.as_str()
+    };
+    f.render_widget(Paragraph::new(status), right[1]);
+}
+
+/// The pairs on screen, with their index in `combos`.
+fn visible<'a, 'b>(app: &'b App<'a>) -> impl Iterator<Item = (usize, &'a EmojiPair)> + 'b {
+    let (_, cols, rows) = app.grid;
+    let start = app.scroll * cols;
+    app.combos
+        .iter()
+        .copied()
+        .enumerate()
+        .skip(start)
+        .take(cols * rows)
+}
+
+/// The on-screen cell of `combos[i]`, clipped to the grid, or `None` if
+/// none of it fits.
+fn cell_rect(app: &App, i: usize) -> Option<Rect> {
+    let (inner, cols, _) = app.grid;
+    let col = (i % cols) as u16;
+    let row = (i / cols - app.scroll) as u16;
+    let cell = Rect {
+        x: inner.x.saturating_add(col.saturating_mul(CELL_WIDTH)),
+        y: inner.y.saturating_add(row.saturating_mul(CELL_HEIGHT)),
+        width: CELL_WIDTH.min(inner.width).saturating_sub(1),
+        height: CELL_HEIGHT.min(inner.height),
+    };
+    clip(cell, inner)
+}
+
+/// The part of `rect` inside `bounds`, or `None` if they don't overlap.
+/// tui's `Rect::intersection` underflows on disjoint rects.
+fn clip(rect: Rect, bounds: Rect) -> Option<Rect> {
+    if rect.intersects(bounds) {
+        Some(rect.intersection(bounds))
+    } else {
+        None
+    }
+}
+
+/// Prints the visible images over the grid cells `ui` left for them.
+fn print_images(app: &App) {
+    for (i, pair) in visible(app) {
+        let Some(path) = app.image_path(pair) else {
+            continue;
+        };
+        // viuer doesn't clip, so only print images that fit whole
+        let Some(cell) = cell_rect(app, i) else {
+            continue;
+        };
+        if cell.width < IMAGE_WIDTH || cell.height < IMAGE_HEIGHT {
+            continue;
+        }
+        let config = viuer::Config {
+            transparent: true,
+            absolute_offset: true,
+            x: cell.x,
+            y: cell.y as i16,
+            width: Some(IMAGE_WIDTH as u32),
+            height: Some(IMAGE_HEIGHT as u32),
+            // graphics protocols draw over the text grid, so clearing the
+            // screen wouldn't remove them when the page changes
+            use_kitty: false,
+            use_iterm: false,
+            ..Default::default()
+        };
+        // a missing or unreadable image just means no preview
+        let _ = viuer::print_from_file(path, &config);
+    }
+}
+
+async fn run<B: Backend>(
+    terminal: &mut Terminal<B>,
+    db: &EmojiDB,
+    options: Options,
+) -> Result<(), Box<dyn Error>> {
+    let mut app = App::new(db, options);
+
+    while !app.quit {
+        // images aren't part of tui's buffer, so a full redraw clears them
+        if app.stale {
+            terminal.clear()?;
+        }
+        terminal.draw(|f| ui(f, &mut app))?;
+        if app.stale {
+            print_images(&app);
+            app.stale = false;
+        }
+
+        match event::read()? {
+            Event::Key(key) => app.on_key(db, key).await,
+            Event::Resize(_, _) => app.stale = true,
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+/// A full-screen browser: every emoji on the left, the selected one's
+/// combinations in a grid on the right.
+pub async fn browse(db: &EmojiDB, options: Options) -> Result<(), Box<dyn Error>> {
+    if db.pairs().is_empty() {
+        return Err(Box::<dyn Error>::from("no pairs to browse"));
+    }
+
+    let _guard = TerminalGuard::enter()?;
+    let mut terminal = Terminal::new(CrosstermBackend::new(io::stdout()))?;
+
+    run(&mut terminal, db, options).await
+}
+
+/// Raw mode and the alternate screen, undone on drop so an error or a
+/// panic doesn't leave the terminal unusable.
+struct TerminalGuard;
+
+impl TerminalGuard {
+    fn enter() -> Result<Self, Box<dyn Error>> {
+        enable_raw_mode()?;
+        let guard = TerminalGuard;
+        execute!(io::stdout(), EnterAlternateScreen)?;
+        Ok(guard)
+    }
+}
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        let _ = disable_raw_mode();
+        let _ = execute!(io::stdout(), LeaveAlternateScreen, Show);
+    }
+}
@@ -0,0 +1,182 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs;
+use std::path::PathBuf;
+
+use serde::Deserialize;
+
+const CONFIG_FILENAME: &str = "config.toml";
+const DEFAULT_PROFILE: &str = "default";
+
+#[derive(Deserialize, Default, Debug)]
+struct ConfigFile {
+    #[serde(default)]
+    slack: HashMap<String, SlackProfile>,
+    #[serde(default)]
+    discord: HashMap<String, DiscordProfile>,
+}
+
+#[derive(Deserialize, Default, Clone, Debug)]
+struct SlackProfile {
+    cookie: Option<String>,
+    token: Option<String>,
+    workspace_name: Option<String>,
+}
+
+#[derive(Deserialize, Default, Clone, Debug)]
+struct DiscordProfile {
+    token: Option<String>,
+    guild_id: Option<String>,
+}
+
+pub struct SlackConfig {
+    pub cookie: String,
+    pub token: String,
+    pub workspace_name: String,
+}
+
+pub struct DiscordConfig {
+    pub token: String,
+    pub guild_id: String,
+}
+
+/// Runtime configuration read from the TOML config file's profiles.
+///
+/// The `default` profile can also be set (and overridden) by environment
+/// variables or a `.env` file in the working directory; named profiles are
+/// only read from the config file.
+pub struct Config {
+    path: Option<PathBuf>,
+    profile: String,
+    file: ConfigFile,
+}
+
+impl Config {
+    pub fn default_path() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join(env!("CARGO_PKG_NAME")).join(CONFIG_FILENAME))
+    }
+
+    /// An explicit `path` must exist; the default path is only read if present.
+    pub fn load(
+        path: &Option<PathBuf>,
+        profile: &Option<String>,
+    ) -> Result<Config, Box<dyn Error>> {
+        // a missing .env is fine, the values may come from elsewhere
+        dotenv::dotenv().ok();
+
+        let path = match path {
+            Some(path) => Some(path.to_owned()),
+            None => Config::default_path().filter(|path| path.is_file()),
+        };
+
+        let file = match &path {
+            Some(path) => {
+                let contents = fs::read_to_string(path)
+                    .map_err(|err| format!("could not read {}: {}", path.display(), err))?;
+                toml::from_str(&contents)
+                    .map_err(|err| format!("could not parse {}: {}", path.display(), err))?
+            }
+            None => ConfigFile::default(),
+        };
+
+        Ok(Config {
+            path,
+            profile: profile.to_owned().unwrap_or(DEFAULT_PROFILE.to_owned()),
+            file,
+        })
+    }
+
+    fn is_default_profile(&self) -> bool {
+        self.profile == DEFAULT_PROFILE
+    }
+
+    fn location(&self) -> String {
+        match &self.path {
+            Some(path) => path.display().to_string(),
+            None => match Config::default_path() {
+                Some(path) => path.display().to_string(),
+                None => "a config file".to_owned(),
+            },
+        }
+    }
+
+    fn missing(&self, section: &str, key: &str, env_key: &str) -> Box<dyn Error> {
+        let setting = format!(
+            "`{}.{}.{}` in {}",
+            section,
+            self.profile,
+            key,
+            self.location()
+        );
+        let message = if self.is_default_profile() {
+            format!(
+                "missing {} {} (set {} or {})",
+                section, key, env_key, setting
+            )
+        } else {
+            format!("missing {} {} (set {})", section, key, setting)
+        };
+        Box::<dyn Error>::from(message)
+    }
+
+    fn resolve(
+        &self,
+        section: &str,
+        key: &str,
+        env_key: &str,
+        value: Option<String>,
+    ) -> Result<String, Box<dyn Error>> {
+        if self.is_default_profile() {
+            if let Ok(value) = std::env::var(env_key) {
+                if !value.is_empty() {
+                    return Ok(value);
+                }
+            }
+        }
+        value.ok_or_else(|| self.missing(section, key, env_key))
+    }
+
+    fn profile<T: Clone + Default>(
+        &self,
+        section: &str,
+        profiles: &HashMap<String, T>,
+    ) -> Result<T, Box<dyn Error>> {
+        match profiles.get(&self.profile) {
+            Some(profile) => Ok(profile.clone()),
+            None if self.is_default_profile() => Ok(T::default()),
+            None => Err(Box::<dyn Error>::from(format!(
+                "{} profile `{}` not found in {}",
+                section,
+                self.profile,
+                self.location()
+            ))),
+        }
+    }
+
+    pub fn slack(&self) -> Result<SlackConfig, Box<dyn Error>> {
+        let profile = self.profile("slack", &self.file.slack)?;
+        Ok(SlackConfig {
+            cookie: self.resolve("slack", "cookie", "COOKIE", profile.cookie)?,
+            token: self.resolve("slack", "token", "TOKEN", profile.token)?,
+            workspace_name: self.resolve(
+                "slack",
+                "workspace_name",
+                "WORKSPACE_NAME",
+                profile.workspace_name,
+            )?,
+        })
+    }
+
+    pub fn discord(&self) -> Result<DiscordConfig, Box<dyn Error>> {
+        let profile = self.profile("discord", &self.file.discord)?;
+        Ok(DiscordConfig {
+            token: self.resolve("discord", "token", "DISCORD_TOKEN", profile.token)?,
+            guild_id: self.resolve(
+                "discord",
+                "guild_id",
+                "DISCORD_GUILD_ID__EC",
+                profile.guild_id,
+            )?,
+        })
+    }
+}
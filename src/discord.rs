@@ -1,28 +1,15 @@
 use std::error::Error;
-use std::path::{Path, PathBuf};
+use std::path::PathBuf;
 
 use serde::{Deserialize, Serialize};
 
-use crate::utils;
-
-#[derive(Deserialize, Debug)]
-pub struct EmojiUser {
-    pub username: String,
-    pub id: String,
-    // "discriminator": "0002",
-    // "avatar": "5500909a3274e1812beb4e8de6631111",
-    // "public_flags": 131328,
-}
+use crate::config::DiscordConfig;
 
+// the API returns more, see https://discord.com/developers/docs/resources/emoji
 #[derive(Deserialize, Debug)]
 pub struct Emoji {
     pub id: String,
     pub name: String,
-    pub roles: Vec<String>, // ["41771983429993000", "41771983429993111"]
-    pub user: EmojiUser,
-    pub require_colons: bool,
-    pub managed: bool,
-    pub animated: bool,
 }
 
 #[derive(Serialize, Debug)]
@@ -40,27 +27,8 @@ fn get_emoji_endpoint(guild_id: &str) -> PathBuf {
     ))
 }
 
-async fn fetch_emoji() -> Result<Vec<Emoji>, Box<dyn Error>> {
-    let endpoint = get_emoji_endpoint(dotenv!("DISCORD_GUILD_ID__EC"));
-    let auth = format!("Bot {}", dotenv!("DISCORD_TOKEN"));
-    let client = reqwest::Client::new();
-    let res = client
-        .get(endpoint.to_str().unwrap())
-        .header("authorization", auth)
-        .send()
-        .await?;
-
-    match res.status() {
-        status if status.is_success() => {
-            let body = res.json::<Vec<Emoji>>().await?;
-            Ok(body)
-        }
-        status => Err(Box::<dyn Error>::from(status.to_string())),
-    }
-}
-
-async fn add_emoji() -> Result<Emoji, Box<dyn Error>> {
-    let bytes = utils::get_file_as_byte_vec(Path::new("dist/4.3e9.1f603.1f603.smiley.smiley.png"));
+async fn add_emoji(config: &DiscordConfig) -> Result<Emoji, Box<dyn Error>> {
+    let bytes = std::fs::read("dist/4.3e9.1f603.1f603.smiley.smiley.png")?;
     let mime_type = "image/png";
     let body = CreateEmoji {
         name: "test_delete".to_owned(),
@@ -70,8 +38,8 @@ async fn add_emoji() -> Result<Emoji, Box<dyn Error>> {
 
     println!("{:#?}", body);
 
-    let endpoint = get_emoji_endpoint(dotenv!("DISCORD_GUILD_ID__EC"));
-    let auth = format!("Bot {}", dotenv!("DISCORD_TOKEN"));
+    let endpoint = get_emoji_endpoint(&config.guild_id);
+    let auth = format!("Bot {}", config.token);
     let client = reqwest::Client::new();
     let res = client
         .post(endpoint.to_str().unwrap())
@@ -86,11 +54,10 @@ async fn add_emoji() -> Result<Emoji, Box<dyn Error>> {
     }
 }
 
-pub async fn discord(name_query: &Option<String>) -> Result<(), Box<dyn Error>> {
-    // let emoji = fetch_emoji().await.unwrap();
-    let emoji = add_emoji().await.unwrap();
+pub async fn discord(config: &DiscordConfig) -> Result<(), Box<dyn Error>> {
+    let emoji = add_emoji(config).await?;
 
-    println!("{:#?}", &emoji);
+    println!("✅ added :{}: ({})", emoji.name, emoji.id);
 
     Ok(())
 }
@@ -0,0 +1,286 @@
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+use emoji_kitchen::emoji;
+use emoji_kitchen::manifest::{Freshness, Manifest};
+use emoji_kitchen::process::{Pipeline, Variant};
+use emoji_kitchen::template::FilenameTemplate;
+use emoji_kitchen::utils::{self, Fetched};
+use futures::StreamExt;
+use indicatif::{HumanBytes, ProgressBar, ProgressStyle};
+
+// how many completed downloads between manifest saves
+const MANIFEST_SAVE_INTERVAL: u64 = 100;
+
+pub struct Options {
+    pub output: PathBuf,
+    /// output path of each pair, relative to `output`
+    pub template: FilenameTemplate,
+    pub concurrency: usize,
+    /// ignore the manifest and download everything
+    pub force: bool,
+    /// seconds before a downloaded file is revalidated with a conditional request
+    pub max_age_secs: u64,
+    /// check downloaded files against their hash, not just their size
+    pub verify: bool,
+    /// variants made of each download, each into its own folder in `output`
+    pub pipeline: Pipeline,
+}
+
+pub async fn download(pairs: Vec<emoji::EmojiPair>, options: Options) {
+    println!("{} pairs found", pairs.len());
+
+    let output_path = options.output.as_path();
+    if let Err(err) = std::fs::create_dir_all(output_path) {
+        println!(
+            "🚫 could not create output dir {}: {}",
+            output_path.display(),
+            err
+        );
+        return;
+    }
+
+    let manifest = match Manifest::load(output_path) {
+        Err(err) => {
+            println!("⚠️ ignoring manifest: {}", err);
+            Manifest::default()
+        }
+        Ok(manifest) => manifest,
+    };
+
+    let mut queue = Vec::new();
+    let mut up_to_date = 0;
+
+    for pair in &pairs {
+        let filename = format!("{}.png", options.template.render(pair));
+        let dest_path = output_path.join(&filename);
+        let freshness = match manifest.files.get(&filename) {
+            Some(_) if options.force => Freshness::Missing,
+            Some(entry) => entry.freshness(
+                &pair.image_url,
+                &dest_path,
+                options.max_age_secs,
+                options.verify,
+            ),
+            None => Freshness::Missing,
+        };
+        match freshness {
+            Freshness::Fresh => up_to_date += 1,
+            Freshness::Stale => queue.push((pair, filename, true)),
+            Freshness::Missing => queue.push((pair, filename, false)),
+        }
+    }
+
+    println!("ℹ️ {} up to date, {} to download", up_to_date, queue.len());
+
+    let client = utils::retrying_client();
+    let manifest = Mutex::new(manifest);
+    let completed = AtomicU64::new(0);
+    let total_bytes = AtomicU64::new(0);
+
+    let progress = ProgressBar::new(queue.len() as u64);
+    progress.set_style(
+        ProgressStyle::with_template(
+            "[{elapsed_precise}] {wide_bar} {pos}/{len} ({per_sec}, {msg}) ETA {eta}",
+        )
+        .unwrap(),
+    );
+
+    let download_iter = queue.into_iter().map(|(pair, filename, revalidate)| {
+        let client = &client;
+        let manifest = &manifest;
+        let progress = &progress;
+        let completed = &completed;
+        let total_bytes = &total_bytes;
+        async move {
+            let dest_path = output_path.join(&filename);
+            let cached = if revalidate {
+                manifest.lock().unwrap().files.get(&filename).cloned()
+            } else {
+                None
+            };
+            let result = match dest_path.parent().map(std::fs::create_dir_all) {
+                Some(Err(err)) => Err(Box::<dyn Error>::from(err)),
+                _ => {
+                    utils::download_and_save_image(
+                        client,
+                        &pair.image_url,
+                        &dest_path,
+                        cached.as_ref(),
+                    )
+                    .await
+                }
+            };
+            progress.inc(1);
+
+            let error = match result {
+                Err(err) => {
+                    progress.println(format!("🚫 {} {} {}", filename, pair.image_url, err));
+                    Some((format!("{} {}", filename, pair.image_url), err))
+                }
+                Ok(Fetched::Unchanged) => {
+                    if let Some(entry) = manifest.lock().unwrap().files.get_mut(&filename) {
+                        entry.timestamp = emoji_kitchen::manifest::now();
+                    }
+                    None
+                }
+                Ok(Fetched::Saved(entry)) => {
+                    let total = total_bytes.fetch_add(entry.size, Ordering::Relaxed) + entry.size;
+                    progress.set_message(HumanBytes(total).to_string());
+                    manifest
+                        .lock()
+                        .unwrap()
+                        .files
+                        .insert(filename.to_owned(), entry);
+                    None
+                }
+            };
+
+            // save along the way so an interrupted run can resume
+            if (completed.fetch_add(1, Ordering::Relaxed) + 1)
+                .is_multiple_of(MANIFEST_SAVE_INTERVAL)
+            {
+                if let Err(err) = manifest.lock().unwrap().save(output_path) {
+                    progress.println(format!("⚠️ could not save manifest: {}", err));
+                }
+            }
+
+            error
+        }
+    });
+
+    let errors: Vec<(String, Box<dyn Error>)> = futures::stream::iter(download_iter)
+        .buffer_unordered(options.concurrency.max(1))
+        .filter_map(|error| async move { error })
+        .collect()
+        .await;
+
+    progress.finish();
+
+    if let Err(err) = manifest.into_inner().unwrap().save(output_path) {
+        println!("⚠️ could not save manifest: {}", err);
+    }
+
+    println!("ℹ️ Completed with {} errors", errors.len());
+
+    for (message, error) in errors {
+        println!("🚫 {} {}", message, error);
+    }
+
+    if !options.pipeline.is_empty() {
+        process(&pairs, &options).await;
+    }
+}
+
+/// Whether `dest` needs to be (re)made from `source`: it's missing, or older.
+fn is_outdated(source: &Path, dest: &Path) -> bool {
+    let modified = |path: &Path| fs::metadata(path).and_then(|meta| meta.modified());
+    match (modified(source), modified(dest)) {
+        (Ok(source), Ok(dest)) => dest < source,
+        _ => true,
+    }
+}
+
+fn render_variants(
+    pipeline: &Pipeline,
+    source: &Path,
+    variants: &[(Variant, PathBuf)],
+) -> Result<(), Box<dyn Error>> {
+    let image = image::open(source)?;
+    for (variant, dest_path) in variants {
+        let bytes = pipeline.render(&image, variant)?;
+        if let Some(parent) = dest_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(dest_path, bytes)?;
+    }
+    Ok(())
+}
+
+/// Runs the downloaded images through `options.pipeline`, skipping variants
+/// that are newer than their download (unless `force`).
+async fn process(pairs: &[emoji::EmojiPair], options: &Options) {
+    let variants = options.pipeline.variants();
+    let mut queue = Vec::new();
+    let mut missing = 0;
+
+    for pair in pairs {
+        let name = options.template.render(pair);
+        let source = options.output.join(format!("{}.png", name));
+        if !source.exists() {
+            missing += 1;
+            continue;
+        }
+        let outdated: Vec<(Variant, PathBuf)> = variants
+            .iter()
+            .map(|variant| {
+                let filename = format!("{}.{}", name, variant.format.extension());
+                (
+                    *variant,
+                    options.output.join(variant.dir_name()).join(filename),
+                )
+            })
+            .filter(|(_, dest_path)| options.force || is_outdated(&source, dest_path))
+            .collect();
+        if !outdated.is_empty() {
+            queue.push((source, outdated));
+        }
+    }
+
+    println!(
+        "ℹ️ Processing {} images into {}",
+        queue.len(),
+        variants
+            .iter()
+            .map(|variant| variant.dir_name())
+            .collect::<Vec<_>>()
+            .join(", ")
+    );
+    if missing > 0 {
+        println!("⚠️ {} pairs weren't downloaded", missing);
+    }
+
+    let progress = ProgressBar::new(queue.len() as u64);
+    progress.set_style(
+        ProgressStyle::with_template("[{elapsed_precise}] {wide_bar} {pos}/{len} ETA {eta}")
+            .unwrap(),
+    );
+
+    let process_iter = queue.into_iter().map(|(source, variants)| {
+        let pipeline = options.pipeline.clone();
+        let progress = &progress;
+        async move {
+            // decoding and encoding is CPU bound, so off the async threads
+            let result = tokio::task::spawn_blocking({
+                let source = source.clone();
+                move || {
+                    render_variants(&pipeline, &source, &variants).map_err(|err| err.to_string())
+                }
+            })
+            .await;
+            progress.inc(1);
+            match result {
+                Ok(Ok(())) => None,
+                Ok(Err(err)) => Some((source, err)),
+                Err(err) => Some((source, err.to_string())),
+            }
+        }
+    });
+
+    let errors: Vec<(PathBuf, String)> = futures::stream::iter(process_iter)
+        .buffer_unordered(options.concurrency.max(1))
+        .filter_map(|error| async move { error })
+        .collect()
+        .await;
+
+    progress.finish();
+
+    println!("ℹ️ Processed with {} errors", errors.len());
+
+    for (source, error) in errors {
+        println!("🚫 {} {}", source.display(), error);
+    }
+}
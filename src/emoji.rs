@@ -1,7 +1,66 @@
 use core::str;
+use std::borrow::Cow;
 use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::path::PathBuf;
 
 use serde::Deserialize;
+use sha2::{Digest, Sha256};
+
+use crate::query::Query;
+use crate::skin_tone::SkinTone;
+
+const EMBEDDED_PAIRS: &[u8] = include_bytes!("./pairs.txt");
+const EMBEDDED_EMOJI_DATA: &[u8] = include_bytes!("./emoji.json");
+
+const PAIRS_FILENAME: &str = "pairs.txt";
+const EMOJI_DATA_FILENAME: &str = "emoji.json";
+
+/// Where the pairs database and emoji data are read from.
+///
+/// Each file is resolved in order: the explicit path (if given), then
+/// `<data dir>/emoji-kitchen-cli/<filename>` (if it exists), then the copy
+/// embedded in the binary.
+#[derive(Clone, Debug, Default)]
+pub struct Sources {
+    pub pairs_file: Option<PathBuf>,
+    pub emoji_data: Option<PathBuf>,
+}
+
+impl Sources {
+    pub fn data_dir() -> Option<PathBuf> {
+        dirs::data_dir().map(|dir| dir.join(env!("CARGO_PKG_NAME")))
+    }
+
+    fn read(
+        explicit: &Option<PathBuf>,
+        filename: &str,
+        embedded: &'static [u8],
+    ) -> Result<Cow<'static, [u8]>, Box<dyn Error>> {
+        if let Some(path) = explicit {
+            return fs::read(path)
+                .map(Cow::Owned)
+                .map_err(|err| format!("could not read {}: {}", path.display(), err).into());
+        }
+
+        match Sources::data_dir().map(|dir| dir.join(filename)) {
+            Some(path) if path.is_file() => fs::read(&path)
+                .map(Cow::Owned)
+                .map_err(|err| format!("could not read {}: {}", path.display(), err).into()),
+            _ => Ok(Cow::Borrowed(embedded)),
+        }
+    }
+
+    pub fn pairs(&self) -> Result<Cow<'static, [u8]>, Box<dyn Error>> {
+        Sources::read(&self.pairs_file, PAIRS_FILENAME, EMBEDDED_PAIRS)
+    }
+
+    pub fn emoji_data(&self) -> Result<Cow<'static, [u8]>, Box<dyn Error>> {
+        Sources::read(&self.emoji_data, EMOJI_DATA_FILENAME, EMBEDDED_EMOJI_DATA)
+    }
+}
 
 fn lowercase_serialize<'de, D>(deserializer: D) -> Result<String, D::Error>
 where
@@ -11,11 +70,48 @@ where
     Ok(s.to_lowercase())
 }
 
+fn lowercase_option_serialize<'de, D>(deserializer: D) -> Result<Option<String>, D::Error>
+where
+    D: serde::de::Deserializer<'de>,
+{
+    let s = Option::<String>::deserialize(deserializer)?;
+    Ok(s.map(|s| s.to_lowercase()))
+}
+
+fn lowercase_vec_serialize<'de, D>(deserializer: D) -> Result<Vec<String>, D::Error>
+where
+    D: serde::de::Deserializer<'de>,
+{
+    let v = Option::<Vec<String>>::deserialize(deserializer)?;
+    Ok(v.unwrap_or_default()
+        .into_iter()
+        .map(|s| s.to_lowercase())
+        .collect())
+}
+
+fn null_vec_serialize<'de, D>(deserializer: D) -> Result<Vec<String>, D::Error>
+where
+    D: serde::de::Deserializer<'de>,
+{
+    Ok(Option::<Vec<String>>::deserialize(deserializer)?.unwrap_or_default())
+}
+
+/// The codepoint of an emoji character, e.g. `🐱` -> `1f431`.
+pub fn codepoint_from_chars(input: &str) -> String {
+    input
+        .chars()
+        .map(|c| format!("{:04x}", c as u32))
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
 #[derive(serde::Deserialize, Clone, Debug)]
 pub struct EmojiSkinVariation {
     #[serde(rename(deserialize = "unified"))]
     #[serde(deserialize_with = "lowercase_serialize")]
     pub codepoint: String,
+    #[serde(default, deserialize_with = "lowercase_option_serialize")]
+    pub non_qualified: Option<String>,
 }
 
 #[derive(serde::Deserialize, Clone, Debug)]
@@ -26,21 +122,145 @@ pub struct Emoji {
     pub name: String,
     #[serde(deserialize_with = "lowercase_serialize")]
     pub short_name: String,
+    /// every short name, including `short_name`, e.g. `["+1", "thumbsup"]`
+    #[serde(default, deserialize_with = "lowercase_vec_serialize")]
+    pub short_names: Vec<String>,
+    /// the codepoint without variation selectors, when it differs
+    #[serde(default, deserialize_with = "lowercase_option_serialize")]
+    pub non_qualified: Option<String>,
+    /// text shortcuts, e.g. `:)`
+    #[serde(default, deserialize_with = "null_vec_serialize")]
+    pub texts: Vec<String>,
     pub category: String,
     pub subcategory: String,
     pub sort_order: u16,
     #[serde(default)]
     pub skin_variations: HashMap<String, EmojiSkinVariation>,
+    /// set on the variants `get_emoji_map` adds for `skin_variations`
+    #[serde(skip)]
+    pub skin_tone: Option<SkinTone>,
+    /// the codepoint of the emoji this is a skin tone variant of
+    #[serde(skip)]
+    pub skin_tone_of: Option<String>,
 }
 
-pub fn get_emoji_map() -> HashMap<String, Emoji> {
-    let json: Vec<Emoji> = serde_json::from_slice(include_bytes!("./emoji.json"))
-        .expect("Failed to find or parse JSON!");
+impl Emoji {
+    /// `short_name` and its aliases.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        std::iter::once(self.short_name.as_str())
+            .chain(self.short_names.iter().map(|name| name.as_str()))
+    }
+
+    /// Whether `codepoint` (e.g. `2764-fe0f` or `2764`) or the emoji
+    /// character itself is this emoji.
+    pub fn has_codepoint(&self, codepoint: &str) -> bool {
+        let codepoint = match codepoint.chars().next() {
+            Some(c) if !c.is_ascii() => codepoint_from_chars(codepoint),
+            _ => codepoint.to_lowercase().replace(['_', ' '], "-"),
+        };
+        self.codepoint == codepoint || self.non_qualified.as_ref() == Some(&codepoint)
+    }
+
+    /// Whether `input` refers to this emoji: a short name or alias (optionally
+    /// wrapped in colons), a text shortcut, the emoji character, or its
+    /// qualified or non-qualified codepoint.
+    pub fn is_named(&self, input: &str) -> bool {
+        let input = input.trim();
+        let name = input
+            .strip_prefix(':')
+            .and_then(|name| name.strip_suffix(':'))
+            .unwrap_or(input);
+        self.names().any(|n| n.eq_ignore_ascii_case(name))
+            || self.texts.iter().any(|text| text == input)
+            || self.has_codepoint(input)
+    }
+
+    /// The variant of this emoji in `tone`, e.g. `wave_skin_tone_2`, if it
+    /// has one. Only variants with the same tone for every person count.
+    pub fn skin_tone_variant<'a>(
+        &self,
+        map: &'a HashMap<String, Emoji>,
+        tone: SkinTone,
+    ) -> Option<&'a Emoji> {
+        self.skin_variations
+            .iter()
+            .find(|(key, _)| SkinTone::from_variation_key(key) == Some(tone))
+            .and_then(|(_, variation)| map.get(&variation.codepoint))
+    }
+
+    /// An emoji for one of `skin_variations`, named after this one.
+    fn with_skin_tone(&self, tone: SkinTone, variation: &EmojiSkinVariation) -> Emoji {
+        let suffix = format!("skin_tone_{}", tone.number());
+        let short_names = self
+            .names()
+            .map(|name| format!("{}_{}", name, suffix))
+            .chain(
+                self.names()
+                    .map(|name| format!("{}::skin-tone-{}", name, tone.number())),
+            )
+            .collect();
+        Emoji {
+            codepoint: variation.codepoint.to_owned(),
+            name: format!("{} {} SKIN TONE", self.name, tone.label().to_uppercase()),
+            short_name: format!("{}_{}", self.short_name, suffix),
+            short_names,
+            non_qualified: variation.non_qualified.to_owned(),
+            texts: Vec::new(),
+            category: self.category.to_owned(),
+            subcategory: self.subcategory.to_owned(),
+            sort_order: self.sort_order,
+            skin_variations: HashMap::new(),
+            skin_tone: Some(tone),
+            skin_tone_of: Some(self.codepoint.to_owned()),
+        }
+    }
+}
+
+#[cfg(test)]
+impl Emoji {
+    /// A bare emoji for tests, with sort order 1 and no aliases.
+    pub(crate) fn test_fixture(codepoint: &str, short_name: &str, category: &str) -> Emoji {
+        serde_json::from_value(serde_json::json!({
+            "unified": codepoint,
+            "name": short_name.to_uppercase().replace('_', " "),
+            "short_name": short_name,
+            "category": category,
+            "subcategory": "",
+            "sort_order": 1,
+        }))
+        .unwrap()
+    }
+}
+
+/// Finds the emoji `input` refers to, see `Emoji::is_named`. Emoji are
+/// preferred over skin tone variants.
+pub fn find_emoji<'a>(map: &'a HashMap<String, Emoji>, input: &str) -> Option<&'a Emoji> {
+    match map.get(&EmojiPair::normalize_codepoint(input.trim())) {
+        Some(emoji) => Some(emoji),
+        None => map
+            .values()
+            .filter(|emoji| emoji.is_named(input))
+            .min_by_key(|emoji| (emoji.skin_tone.is_some(), emoji.sort_order)),
+    }
+}
+
+/// Every emoji, keyed by codepoint, including a variant for each skin tone
+/// in `skin_variations`.
+pub fn get_emoji_map(sources: &Sources) -> Result<HashMap<String, Emoji>, Box<dyn Error>> {
+    let json: Vec<Emoji> = serde_json::from_slice(&sources.emoji_data()?)?;
     let mut map = HashMap::new();
     for emoji in json {
-        map.insert(emoji.codepoint.to_string(), emoji.clone());
+        for (key, variation) in &emoji.skin_variations {
+            if let Some(tone) = SkinTone::from_variation_key(key) {
+                map.insert(
+                    variation.codepoint.to_owned(),
+                    emoji.with_skin_tone(tone, variation),
+                );
+            }
+        }
+        map.insert(emoji.codepoint.to_string(), emoji);
     }
-    map
+    Ok(map)
 }
 
 #[derive(Debug)]
@@ -52,10 +272,15 @@ pub struct EmojiPair {
     pub image_url: String,
     pub filename: String,
     pub sort_order: u16,
+    /// 0 for the newest artwork of this base and pair, 1 for the one before, ...
+    pub revision: usize,
+    /// how many date revisions `pairs.txt` has for this base and pair
+    pub revisions: usize,
 }
 
 impl EmojiPair {
-    fn generate_name(base: &Emoji, pair: &Emoji) -> String {
+    /// The pair's name, e.g. `cat_pizza`.
+    pub fn generate_name(base: &Emoji, pair: &Emoji) -> String {
         format!("{}_{}", base.short_name, pair.short_name)
     }
 
@@ -69,8 +294,20 @@ impl EmojiPair {
         }
     }
 
-    fn generate_image_url(d: &str, base: &Emoji, pair: &Emoji) -> String {
-        let date = i64::from_str_radix(d, 16).unwrap() + 20200000;
+    fn parse_date(d: &str) -> Option<i64> {
+        // from_str_radix alone would take a sign, e.g. "-3e9"
+        if d.is_empty() || !d.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return None;
+        }
+        i64::from_str_radix(d, 16)
+            .ok()
+            .and_then(|date| date.checked_add(20200000))
+    }
+
+    /// The Emoji Kitchen image of `base` and `pair` with the date code `d`.
+    /// Panics if `d` isn't a hex date code.
+    pub fn generate_image_url(d: &str, base: &Emoji, pair: &Emoji) -> String {
+        let date = EmojiPair::parse_date(d).expect("date code should be validated before use");
         let c1 = EmojiPair::normalize_image_url_codepoint(date, &base.codepoint);
         let c2 = EmojiPair::normalize_image_url_codepoint(date, &pair.codepoint);
         format!(
@@ -78,7 +315,8 @@ impl EmojiPair {
         )
     }
 
-    fn generate_filename(d: &str, base: &Emoji, pair: &Emoji, sort_order: u16) -> String {
+    /// The default download filename, see `template::DEFAULT_TEMPLATE`.
+    pub fn generate_filename(d: &str, base: &Emoji, pair: &Emoji, sort_order: u16) -> String {
         format!(
             "{}.{}.{}.{}.{}.{}.png",
             sort_order, d, base.codepoint, pair.codepoint, base.short_name, pair.short_name
@@ -91,13 +329,30 @@ impl EmojiPair {
             d: d.to_string(),
             base: base.clone(),
             pair: pair.clone(),
-            name: EmojiPair::generate_name(&base, &pair),
-            image_url: EmojiPair::generate_image_url(&d, &base, &pair),
-            filename: EmojiPair::generate_filename(&d, &base, &pair, sort_order),
+            name: EmojiPair::generate_name(base, pair),
+            image_url: EmojiPair::generate_image_url(d, base, pair),
+            filename: EmojiPair::generate_filename(d, base, pair, sort_order),
             sort_order,
+            revision: 0,
+            revisions: 1,
+        }
+    }
+
+    /// Older revisions get the date appended to their name, so every
+    /// revision of a combination has a distinct name.
+    fn set_revision(&mut self, revision: usize, revisions: usize) {
+        self.revision = revision;
+        self.revisions = revisions;
+        if revision > 0 {
+            self.name = format!("{}_{}", self.name, self.date());
         }
     }
 
+    /// The `YYYYMMDD` date the artwork was published, decoded from `d`.
+    pub fn date(&self) -> i64 {
+        EmojiPair::parse_date(&self.d).expect("date code should be validated before use")
+    }
+
     fn normalize_codepoint(codepoint: &str) -> String {
         match codepoint.to_lowercase().as_str() {
             // pairs.txt doesn't include "00" for some codepoints — so this adds them
@@ -107,59 +362,578 @@ impl EmojiPair {
         }
     }
 
-    pub fn from_pair_string(pair: &str, map: &HashMap<String, Emoji>) -> Self {
+    pub fn from_pair_string(
+        pair: &str,
+        map: &HashMap<String, Emoji>,
+    ) -> Result<Self, PairErrorKind> {
         // pair may end with "/", so 4 is used
         let mut split = pair.splitn(4, '/');
-        let d = split.next().unwrap();
-        let codepoint1 = EmojiPair::normalize_codepoint(split.next().unwrap());
-        let codepoint2 = EmojiPair::normalize_codepoint(split.next().unwrap());
-        let base = map.get(&codepoint1).expect(&format!(
-            "⚠️ [BASE] emoji data for {} not found!",
-            codepoint1
-        ));
-        let pair = map.get(&codepoint2).expect(&format!(
-            "⚠️ [PAIR] emoji data for {} not found!",
-            codepoint1
-        ));
-        EmojiPair::new(d, &base, &pair)
+        let (d, codepoint1, codepoint2) = match (split.next(), split.next(), split.next()) {
+            (Some(d), Some(c1), Some(c2)) if !c1.is_empty() && !c2.is_empty() => (d, c1, c2),
+            _ => return Err(PairErrorKind::Malformed),
+        };
+        match split.next() {
+            Some(rest) if !rest.trim_end_matches('/').is_empty() => {
+                return Err(PairErrorKind::ExtraFields(rest.to_string()))
+            }
+            _ => {}
+        }
+        if EmojiPair::parse_date(d).is_none() {
+            return Err(PairErrorKind::InvalidDate(d.to_string()));
+        }
+        let codepoint1 = EmojiPair::normalize_codepoint(codepoint1);
+        let codepoint2 = EmojiPair::normalize_codepoint(codepoint2);
+        let base = map
+            .get(&codepoint1)
+            .ok_or(PairErrorKind::UnknownCodepoint(PairSide::Base, codepoint1))?;
+        let pair = map
+            .get(&codepoint2)
+            .ok_or(PairErrorKind::UnknownCodepoint(PairSide::Pair, codepoint2))?;
+        Ok(EmojiPair::new(d, base, pair))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PairSide {
+    Base,
+    Pair,
+}
+
+impl fmt::Display for PairSide {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PairSide::Base => write!(f, "BASE"),
+            PairSide::Pair => write!(f, "PAIR"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PairErrorKind {
+    /// the line doesn't have the `date/codepoint/codepoint/` shape
+    Malformed,
+    /// fields after the two codepoints
+    ExtraFields(String),
+    /// the date field isn't a hex date code
+    InvalidDate(String),
+    /// a codepoint has no entry in the emoji data
+    UnknownCodepoint(PairSide, String),
+}
+
+impl fmt::Display for PairErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PairErrorKind::Malformed => write!(f, "malformed line"),
+            PairErrorKind::ExtraFields(rest) => write!(f, "unexpected fields {:?}", rest),
+            PairErrorKind::InvalidDate(d) => write!(f, "invalid date code {:?}", d),
+            PairErrorKind::UnknownCodepoint(side, codepoint) => {
+                write!(f, "[{}] emoji data for {} not found", side, codepoint)
+            }
+        }
+    }
+}
+
+/// A line of `pairs.txt` that couldn't be turned into an `EmojiPair`.
+#[derive(Debug, Clone)]
+pub struct PairError {
+    /// 1-based line number in `pairs.txt`
+    pub line: usize,
+    pub raw: String,
+    pub kind: PairErrorKind,
+}
+
+impl fmt::Display for PairError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {} ({:?})", self.line, self.kind, self.raw)
     }
 }
 
+impl Error for PairError {}
+
+/// Parses every non-empty line of `pairs.txt`, keeping the line number of
+/// each pair and collecting the lines that fail instead of stopping on them.
+pub fn parse_pairs(
+    text: &str,
+    map: &HashMap<String, Emoji>,
+) -> (Vec<(usize, EmojiPair)>, Vec<PairError>) {
+    let mut pairs = Vec::new();
+    let mut errors = Vec::new();
+
+    for (index, raw) in text.lines().enumerate() {
+        let line = raw.trim();
+        if line.is_empty() {
+            continue;
+        }
+        match EmojiPair::from_pair_string(line, map) {
+            Ok(pair) => pairs.push((index + 1, pair)),
+            Err(kind) => errors.push(PairError {
+                line: index + 1,
+                raw: raw.to_string(),
+                kind,
+            }),
+        }
+    }
+
+    (pairs, errors)
+}
+
+/// The order two emoji are listed in when the order doesn't matter: lower
+/// `sort_order` first, then lower codepoint.
+pub fn canonical<'a>(a: &'a Emoji, b: &'a Emoji) -> (&'a Emoji, &'a Emoji) {
+    if (a.sort_order, &a.codepoint) <= (b.sort_order, &b.codepoint) {
+        (a, b)
+    } else {
+        (b, a)
+    }
+}
+
+/// `emoji` in `tone`, then `emoji` itself, in order of preference. Variants
+/// in another tone have no candidates.
+pub fn skin_tone_candidates<'a>(
+    map: &'a HashMap<String, Emoji>,
+    emoji: &'a Emoji,
+    tone: SkinTone,
+) -> Vec<&'a Emoji> {
+    match emoji.skin_tone {
+        Some(t) if t == tone => vec![emoji],
+        Some(_) => Vec::new(),
+        None => emoji
+            .skin_tone_variant(map, tone)
+            .into_iter()
+            .chain(std::iter::once(emoji))
+            .collect(),
+    }
+}
+
+/// The base and pair codepoints of the combination that stands in for `base`
+/// and `pair` in `tone`: the one with the most toned sides that `exists`.
+fn skin_tone_key<F>(
+    map: &HashMap<String, Emoji>,
+    base: &Emoji,
+    pair: &Emoji,
+    tone: SkinTone,
+    exists: F,
+) -> Option<(String, String)>
+where
+    F: Fn(&Emoji, &Emoji) -> bool,
+{
+    let pairs = skin_tone_candidates(map, pair, tone);
+    skin_tone_candidates(map, base, tone)
+        .into_iter()
+        .flat_map(|b| pairs.iter().map(move |p| (b, *p)))
+        .find(|(b, p)| exists(b, p))
+        .map(|(b, p)| (b.codepoint.to_owned(), p.codepoint.to_owned()))
+}
+
+fn canonical_key(a: &Emoji, b: &Emoji) -> (String, String) {
+    let (a, b) = canonical(a, b);
+    (a.codepoint.to_owned(), b.codepoint.to_owned())
+}
+
+/// Identifies the `pairs.txt` a db was loaded from.
+#[derive(Clone, Debug)]
+pub struct PairsRevision {
+    pub sha256: String,
+    /// the newest `YYYYMMDD` date in the file
+    pub latest_date: i64,
+}
+
 pub struct EmojiDB {
-    pub pairs: Vec<EmojiPair>,
+    /// private, so it can't get out of step with `index`
+    pairs: Vec<EmojiPair>,
+    /// all emoji data, keyed by codepoint
+    pub emoji_map: HashMap<String, Emoji>,
+    /// lines of `pairs.txt` that were skipped
+    pub errors: Vec<PairError>,
+    pub revision: PairsRevision,
+    /// indexes into `pairs` by canonical codepoints, newest first
+    index: HashMap<(String, String), Vec<usize>>,
+}
+
+impl EmojiDB {
+    fn new(
+        pairs: Vec<EmojiPair>,
+        emoji_map: HashMap<String, Emoji>,
+        errors: Vec<PairError>,
+        revision: PairsRevision,
+    ) -> Self {
+        let mut db = EmojiDB {
+            pairs,
+            emoji_map,
+            errors,
+            revision,
+            index: HashMap::new(),
+        };
+        db.build_index();
+        db
+    }
+
+    fn build_index(&mut self) {
+        let mut index: HashMap<(String, String), Vec<usize>> = HashMap::new();
+        for (i, pair) in self.pairs.iter().enumerate() {
+            index
+                .entry(canonical_key(&pair.base, &pair.pair))
+                .or_default()
+                .push(i);
+        }
+        for indexes in index.values_mut() {
+            indexes.sort_by_key(|i| std::cmp::Reverse(self.pairs[*i].date()));
+        }
+        self.index = index;
+    }
+
+    /// Every loaded pair, in sort order.
+    pub fn pairs(&self) -> &[EmojiPair] {
+        &self.pairs
+    }
+
+    pub fn into_pairs(self) -> Vec<EmojiPair> {
+        self.pairs
+    }
+
+    /// Every loaded pair of `a` and `b`, in either order, newest first.
+    pub fn get_all(&self, a: &Emoji, b: &Emoji) -> Vec<&EmojiPair> {
+        match self.index.get(&canonical_key(a, b)) {
+            Some(indexes) => indexes.iter().map(|i| &self.pairs[*i]).collect(),
+            None => Vec::new(),
+        }
+    }
+
+    /// The newest pair of `a` and `b`, in either order.
+    pub fn get(&self, a: &Emoji, b: &Emoji) -> Option<&EmojiPair> {
+        self.get_all(a, b).into_iter().next()
+    }
+
+    /// The newest pair of `a` and `b` in `tone`, falling back to `a` or `b`
+    /// alone in `tone` and then to `get(a, b)`.
+    pub fn get_with_skin_tone(
+        &self,
+        a: &Emoji,
+        b: &Emoji,
+        tone: Option<SkinTone>,
+    ) -> Option<&EmojiPair> {
+        let tone = match tone {
+            Some(tone) => tone,
+            None => return self.get(a, b),
+        };
+        let (base, pair) =
+            skin_tone_key(&self.emoji_map, a, b, tone, |a, b| self.get(a, b).is_some())?;
+        self.get(&self.emoji_map[&base], &self.emoji_map[&pair])
+    }
+
+    /// Loaded pairs that `emoji` is the base or pair of.
+    pub fn pairs_with(&self, emoji: &Emoji) -> impl Iterator<Item = &EmojiPair> {
+        let codepoint = emoji.codepoint.to_owned();
+        self.pairs
+            .iter()
+            .filter(move |p| p.base.codepoint == codepoint || p.pair.codepoint == codepoint)
+    }
+
+    /// The loaded revisions of a base and pair, newest first.
+    pub fn revisions(&self, base: &Emoji, pair: &Emoji) -> Vec<&EmojiPair> {
+        self.get_all(base, pair)
+            .into_iter()
+            .filter(|p| p.base.codepoint == base.codepoint && p.pair.codepoint == pair.codepoint)
+            .collect()
+    }
+
+    /// Drops mirrored entries, e.g. `pizza_cat` when `cat_pizza` is also
+    /// loaded. The order with the newest artwork is kept, or the canonical
+    /// order if both are as new.
+    pub fn dedupe_mirrored(&mut self) {
+        let mut keep: HashMap<(String, String), String> = HashMap::new();
+        for indexes in self.index.values() {
+            let newest = indexes
+                .iter()
+                .map(|i| &self.pairs[*i])
+                .max_by_key(|p| {
+                    (
+                        p.date(),
+                        canonical(&p.base, &p.pair).0.codepoint == p.base.codepoint,
+                    )
+                })
+                .expect("index entries are never empty");
+            keep.insert(
+                canonical_key(&newest.base, &newest.pair),
+                newest.base.codepoint.to_owned(),
+            );
+        }
+        self.pairs
+            .retain(|p| keep.get(&canonical_key(&p.base, &p.pair)) == Some(&p.base.codepoint));
+        self.build_index();
+    }
+}
+
+/// Which date revisions of a combination to load when `pairs.txt` lists the
+/// same base and pair more than once.
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Revisions {
+    /// only the newest artwork
+    #[default]
+    Latest,
+    /// every revision, older ones named `<name>_<date>`
+    All,
 }
 
 pub struct Options {
-    pub name: Option<String>,
+    pub query: Option<Query>,
+    pub revisions: Revisions,
+    pub sources: Sources,
+    /// fail on the first invalid line of `pairs.txt` instead of skipping it
+    pub strict: bool,
+    /// use the variants in this tone where `pairs.txt` has them, and drop
+    /// variants in other tones
+    pub skin_tone: Option<SkinTone>,
 }
 
-pub fn init(options: Options) -> EmojiDB {
-    let pairs_list: Vec<&str> = str::from_utf8(include_bytes!("./pairs.txt"))
-        .unwrap()
-        .trim()
-        .split("\n")
-        .collect();
+pub fn init(options: Options) -> Result<EmojiDB, Box<dyn Error>> {
+    let pairs_bytes = options.sources.pairs()?;
+    let emoji_map = get_emoji_map(&options.sources)?;
+    load(&pairs_bytes, emoji_map, &options)
+}
 
-    let emoji_map = get_emoji_map();
+/// `init` with the sources already read.
+fn load(
+    pairs_bytes: &[u8],
+    emoji_map: HashMap<String, Emoji>,
+    options: &Options,
+) -> Result<EmojiDB, Box<dyn Error>> {
+    let (parsed, errors) = parse_pairs(str::from_utf8(pairs_bytes)?, &emoji_map);
+    let revision = PairsRevision {
+        sha256: format!("{:x}", Sha256::digest(pairs_bytes)),
+        latest_date: parsed
+            .iter()
+            .map(|(_, emoji_pair)| emoji_pair.date())
+            .max()
+            .unwrap_or_default(),
+    };
 
-    let mut pairs: Vec<EmojiPair> = Vec::new();
+    if options.strict {
+        if let Some(error) = errors.first() {
+            return Err(Box::new(error.clone()));
+        }
+    }
 
-    for pair in pairs_list {
-        let emoji_pair = EmojiPair::from_pair_string(pair, &emoji_map);
+    let mut groups: HashMap<(String, String), Vec<(usize, EmojiPair)>> = HashMap::new();
 
-        match &options.name {
-            Some(name) => {
-                if emoji_pair.base.short_name.eq(name) || emoji_pair.pair.short_name.eq(name) {
-                    pairs.push(emoji_pair);
-                }
+    for (line, emoji_pair) in parsed {
+        let key = (
+            emoji_pair.base.codepoint.to_owned(),
+            emoji_pair.pair.codepoint.to_owned(),
+        );
+        groups.entry(key).or_default().push((line, emoji_pair));
+    }
+
+    let mut pairs: Vec<(usize, EmojiPair)> = Vec::new();
+    let exists = |base: &Emoji, pair: &Emoji| {
+        groups.contains_key(&(base.codepoint.to_owned(), pair.codepoint.to_owned()))
+    };
+    let toned_out: Vec<(String, String)> = match options.skin_tone {
+        Some(tone) => groups
+            .iter()
+            .filter(|(key, revisions)| {
+                let (_, emoji_pair) = &revisions[0];
+                skin_tone_key(&emoji_map, &emoji_pair.base, &emoji_pair.pair, tone, exists).as_ref()
+                    != Some(*key)
+            })
+            .map(|(key, _)| key.to_owned())
+            .collect(),
+        None => Vec::new(),
+    };
+    for key in &toned_out {
+        groups.remove(key);
+    }
+
+    for (_, mut revisions) in groups {
+        revisions.sort_by_key(|(_, emoji_pair)| std::cmp::Reverse(emoji_pair.date()));
+        let count = revisions.len();
+
+        for (revision, (line, mut emoji_pair)) in revisions.into_iter().enumerate() {
+            if revision > 0 && options.revisions == Revisions::Latest {
+                break;
             }
-            None => {
-                pairs.push(emoji_pair);
+            emoji_pair.set_revision(revision, count);
+
+            match &options.query {
+                Some(query) => {
+                    if query.matches(&emoji_pair) {
+                        pairs.push((line, emoji_pair));
+                    }
+                }
+                None => {
+                    pairs.push((line, emoji_pair));
+                }
             }
         }
     }
 
-    pairs.sort_by(|a, b| a.sort_order.cmp(&b.sort_order));
+    // the line number keeps ties in pairs.txt order
+    pairs.sort_by_key(|(line, emoji_pair)| (emoji_pair.sort_order, *line));
+    let pairs = pairs
+        .into_iter()
+        .map(|(_, emoji_pair)| emoji_pair)
+        .collect();
+
+    Ok(EmojiDB::new(pairs, emoji_map, errors, revision))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn emoji_map() -> HashMap<String, Emoji> {
+        let cat = Emoji::test_fixture("1F431", "cat", "Animals & Nature");
+        let mut pizza = Emoji::test_fixture("1F355", "pizza", "Food & Drink");
+        pizza.sort_order = 2;
+        [cat, pizza]
+            .into_iter()
+            .map(|emoji| (emoji.codepoint.to_owned(), emoji))
+            .collect()
+    }
+
+    fn db(pairs_txt: &str, revisions: Revisions) -> EmojiDB {
+        let options = Options {
+            query: None,
+            revisions,
+            sources: Sources::default(),
+            strict: true,
+            skin_tone: None,
+        };
+        load(pairs_txt.as_bytes(), emoji_map(), &options).unwrap()
+    }
+
+    fn names<'a>(pairs: impl IntoIterator<Item = &'a EmojiPair>) -> Vec<&'a str> {
+        pairs.into_iter().map(|pair| pair.name.as_str()).collect()
+    }
+
+    /// The line and kind of each error `parse_pairs` collects from `text`.
+    fn errors(text: &str) -> Vec<(usize, PairErrorKind)> {
+        let (_, errors) = parse_pairs(text, &emoji_map());
+        errors.into_iter().map(|e| (e.line, e.kind)).collect()
+    }
+
+    #[test]
+    fn parses_pairs_with_their_line_numbers() {
+        let (pairs, errors) =
+            parse_pairs("3e9/1f431/1f355/\n\n  5209/1F355/1F431  \n", &emoji_map());
+        assert!(errors.is_empty());
+        let parsed: Vec<(usize, &str, i64)> = pairs
+            .iter()
+            .map(|(line, pair)| (*line, pair.name.as_str(), pair.date()))
+            .collect();
+        assert_eq!(
+            parsed,
+            [(1, "cat_pizza", 20201001), (3, "pizza_cat", 20221001)]
+        );
+    }
+
+    #[test]
+    fn collects_each_kind_of_error() {
+        let text = "3e9/1f431\n\
+                    3e9/1f431//\n\
+                    3e9/1f431/1f355/extra/\n\
+                    -3e9/1f431/1f355/\n\
+                    xyz/1f431/1f355/\n\
+                    /1f431/1f355/\n\
+                    3e9/1f436/1f355/\n\
+                    3e9/1f431/1f436/\n";
+        assert_eq!(
+            errors(text),
+            [
+                (1, PairErrorKind::Malformed),
+                (2, PairErrorKind::Malformed),
+                (3, PairErrorKind::ExtraFields("extra/".to_owned())),
+                (4, PairErrorKind::InvalidDate("-3e9".to_owned())),
+                (5, PairErrorKind::InvalidDate("xyz".to_owned())),
+                (6, PairErrorKind::InvalidDate("".to_owned())),
+                (
+                    7,
+                    PairErrorKind::UnknownCodepoint(PairSide::Base, "1f436".to_owned())
+                ),
+                (
+                    8,
+                    PairErrorKind::UnknownCodepoint(PairSide::Pair, "1f436".to_owned())
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_signed_and_overflowing_dates() {
+        assert_eq!(EmojiPair::parse_date("3e9"), Some(20201001));
+        assert_eq!(EmojiPair::parse_date("+3e9"), None);
+        assert_eq!(EmojiPair::parse_date("-3e9"), None);
+        assert_eq!(EmojiPair::parse_date("7fffffffffffffff"), None);
+    }
+
+    // cat_pizza from 2020-10-01, 2022-10-01 and 2020-01-01
+    const REVISED: &str = "3e9/1f431/1f355/\n5209/1f431/1f355/\n65/1f431/1f355/\n";
 
-    EmojiDB { pairs }
+    #[test]
+    fn latest_keeps_only_the_newest_revision() {
+        let db = db(REVISED, Revisions::Latest);
+        let [pair] = db.pairs() else {
+            panic!("expected one pair, got {}", db.pairs().len());
+        };
+        assert_eq!(pair.name, "cat_pizza");
+        assert_eq!(pair.date(), 20221001);
+        assert_eq!((pair.revision, pair.revisions), (0, 3));
+    }
+
+    #[test]
+    fn all_keeps_every_revision_named_by_date() {
+        let db = db(REVISED, Revisions::All);
+        // same sort order, so pairs.txt order
+        assert_eq!(
+            names(db.pairs()),
+            ["cat_pizza_20201001", "cat_pizza", "cat_pizza_20200101"]
+        );
+
+        let (cat, pizza) = (&db.emoji_map["1f431"], &db.emoji_map["1f355"]);
+        let revisions = db.revisions(cat, pizza);
+        assert_eq!(
+            names(revisions.iter().copied()),
+            ["cat_pizza", "cat_pizza_20201001", "cat_pizza_20200101"]
+        );
+        let numbers: Vec<(usize, usize)> = revisions
+            .iter()
+            .map(|pair| (pair.revision, pair.revisions))
+            .collect();
+        assert_eq!(numbers, [(0, 3), (1, 3), (2, 3)]);
+        assert!(db.revisions(pizza, cat).is_empty());
+    }
+
+    #[test]
+    fn lookups_ignore_the_order_of_the_emoji() {
+        let db = db("3e9/1f431/1f355/\n", Revisions::Latest);
+        let (cat, pizza) = (&db.emoji_map["1f431"], &db.emoji_map["1f355"]);
+        let (a, b) = (db.get(cat, pizza).unwrap(), db.get(pizza, cat).unwrap());
+        assert!(std::ptr::eq(a, b));
+        assert_eq!(a.name, "cat_pizza");
+        assert_eq!(canonical(pizza, cat).0.codepoint, "1f431");
+    }
+
+    #[test]
+    fn mirrored_pairs_are_found_newest_first_and_deduped() {
+        // pizza_cat is newer
+        let mut db = db("3e9/1f431/1f355/\n5209/1f355/1f431/\n", Revisions::Latest);
+        let (cat, pizza) = (&db.emoji_map["1f431"], &db.emoji_map["1f355"]);
+        assert_eq!(names(db.get_all(cat, pizza)), ["pizza_cat", "cat_pizza"]);
+        assert_eq!(names(db.get_all(pizza, cat)), ["pizza_cat", "cat_pizza"]);
+        assert_eq!(db.get(cat, pizza).unwrap().name, "pizza_cat");
+
+        db.dedupe_mirrored();
+        assert_eq!(names(db.pairs()), ["pizza_cat"]);
+        let (cat, pizza) = (&db.emoji_map["1f431"], &db.emoji_map["1f355"]);
+        assert_eq!(names(db.get_all(cat, pizza)), ["pizza_cat"]);
+    }
+
+    #[test]
+    fn dedupe_keeps_the_canonical_order_on_a_tie() {
+        let mut db = db("3e9/1f355/1f431/\n3e9/1f431/1f355/\n", Revisions::Latest);
+        db.dedupe_mirrored();
+        // cat has the lower sort order
+        assert_eq!(names(db.pairs()), ["cat_pizza"]);
+    }
 }
@@ -0,0 +1,153 @@
+use std::error::Error;
+use std::path::{Path, PathBuf};
+
+use emoji_kitchen::emoji::EmojiPair;
+use emoji_kitchen::manifest::Manifest;
+use emoji_kitchen::sheet::{self, SheetOptions};
+use image::{DynamicImage, Rgba};
+
+// width of each preview in terminal cells, like the list preview
+const TILE_CELLS: u32 = 16;
+// pixels per cell of a tile composed for the terminal
+const CELL_PIXELS: u32 = 4;
+// used for a sheet when the terminal width is unknown
+const DEFAULT_COLUMNS: u32 = 8;
+const SHEET_TILE_SIZE: u32 = 128;
+// largest width and height of a sheet page, in pixels
+const MAX_SHEET_SIZE: u32 = 4096;
+
+fn terminal_options() -> SheetOptions {
+    SheetOptions {
+        columns: 1,
+        tile_size: TILE_CELLS * CELL_PIXELS,
+        labels: false,
+        background: None,
+    }
+}
+
+/// Cells per tile, including its padding.
+fn slot_cells() -> u32 {
+    terminal_options().slot_size().0 / CELL_PIXELS
+}
+
+/// How many previews fit across the terminal.
+pub fn columns() -> u32 {
+    match crossterm::terminal::size() {
+        Ok((width, _)) => (width as u32 / slot_cells()).max(1),
+        Err(_) => DEFAULT_COLUMNS,
+    }
+}
+
+/// Where each pair's image was downloaded, whatever template it used.
+fn image_paths(pairs: &[EmojiPair], input_path: &Path) -> Vec<PathBuf> {
+    // a missing or unreadable manifest just means default filenames
+    let files = Manifest::load(input_path)
+        .unwrap_or_default()
+        .filenames_by_url();
+    pairs
+        .iter()
+        .map(|pair| input_path.join(files.get(&pair.image_url).unwrap_or(&pair.filename)))
+        .collect()
+}
+
+fn load(path: &Path) -> Option<DynamicImage> {
+    // a missing or unreadable image just leaves its tile empty
+    image::open(path).ok()
+}
+
+/// Prints the pairs' previews `columns` per row, each labeled with its name.
+pub fn print_grid(pairs: &[EmojiPair], input_path: &Path, columns: u32) {
+    let slot = slot_cells() as usize;
+    let columns = columns.max(1) as usize;
+    let paths = image_paths(pairs, input_path);
+    for (row, paths) in pairs.chunks(columns).zip(paths.chunks(columns)) {
+        let images: Vec<Option<DynamicImage>> = paths.iter().map(|path| load(path)).collect();
+        let tiles: Vec<(Option<&DynamicImage>, &str)> = images
+            .iter()
+            .zip(row)
+            .map(|(image, pair)| (image.as_ref(), pair.name.as_str()))
+            .collect();
+        let options = SheetOptions {
+            columns: row.len() as u32,
+            ..terminal_options()
+        };
+        let image = DynamicImage::ImageRgba8(sheet::contact_sheet(&tiles, options));
+        let config = viuer::Config {
+            transparent: true,
+            absolute_offset: false,
+            width: Some(slot_cells() * row.len() as u32),
+            ..Default::default()
+        };
+        let _ = viuer::print(&image, &config);
+
+        let labels: String = row
+            .iter()
+            .map(|pair| {
+                let name: String = pair.name.chars().take(slot - 1).collect();
+                format!("{:<width$}", name, width = slot)
+            })
+            .collect();
+        println!("{}", labels.trim_end());
+    }
+}
+
+/// Writes the pairs' previews to PNG contact sheets, `columns` per row, each
+/// labeled with its name. Pages are at most `MAX_SHEET_SIZE` pixels a side:
+/// `output` if one is enough, otherwise `<output>-0.png`, `<output>-1.png`, ...
+/// Returns the pages written, none if no pair has been downloaded.
+pub fn write_sheet(
+    pairs: &[EmojiPair],
+    input_path: &Path,
+    columns: u32,
+    output: &Path,
+) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    let paths = image_paths(pairs, input_path);
+    if !paths.iter().any(|path| path.exists()) {
+        return Ok(Vec::new());
+    }
+
+    let options = SheetOptions {
+        columns,
+        tile_size: SHEET_TILE_SIZE,
+        labels: true,
+        background: Some(Rgba([255, 255, 255, 255])),
+    };
+    let (slot_width, slot_height) = options.slot_size();
+    let options = SheetOptions {
+        columns: columns.clamp(1, (MAX_SHEET_SIZE / slot_width).max(1)),
+        ..options
+    };
+    let per_page = (options.columns * (MAX_SHEET_SIZE / slot_height).max(1)) as usize;
+    let page_count = pairs.len().div_ceil(per_page);
+
+    if let Some(parent) = output.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let mut written = Vec::new();
+    let pages = pairs.chunks(per_page).zip(paths.chunks(per_page));
+    for (page, (pairs, paths)) in pages.enumerate() {
+        // one image at a time, so a large sheet doesn't hold every image
+        let mut sheet = sheet::blank_sheet(pairs.len(), options);
+        for (i, (pair, path)) in pairs.iter().zip(paths).enumerate() {
+            sheet::draw_tile(&mut sheet, i, load(path).as_ref(), &pair.name, options);
+        }
+        let path = page_path(output, page, page_count);
+        sheet.save(&path)?;
+        written.push(path);
+    }
+    Ok(written)
+}
+
+fn page_path(output: &Path, page: usize, page_count: usize) -> PathBuf {
+    if page_count == 1 {
+        return output.to_path_buf();
+    }
+    let stem = output.file_stem().unwrap_or_default().to_string_lossy();
+    let extension = output.extension().unwrap_or_default().to_string_lossy();
+    let extension = if extension.is_empty() {
+        "png".into()
+    } else {
+        extension
+    };
+    output.with_file_name(format!("{}-{}.{}", stem, page, extension))
+}
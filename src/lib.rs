@@ -0,0 +1,25 @@
+//! Emoji Kitchen data: loading the pairs database, querying it, building
+//! image urls and exporting it, plus helpers for downloading the images.
+//!
+//! The `network` feature enables the download helpers in [`utils`], `image`
+//! enables animation resizing ([`animation`]), contact sheets ([`sheet`]), sprite sheets
+//! ([`atlas`]) and post-processing downloads ([`process`]), `optimize` and
+//! `avif` add PNG optimization and AVIF output to that, `schema` the JSON
+//! Schema of the export (`write::json_schema`). `cli` (the default) enables
+//! all of them, plus what only the command line tool needs.
+
+#[cfg(feature = "image")]
+pub mod animation;
+#[cfg(feature = "image")]
+pub mod atlas;
+pub mod emoji;
+pub mod manifest;
+#[cfg(feature = "image")]
+pub mod process;
+pub mod query;
+#[cfg(feature = "image")]
+pub mod sheet;
+pub mod skin_tone;
+pub mod template;
+pub mod utils;
+pub mod write;
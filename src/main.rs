@@ -1,24 +1,48 @@
-#[macro_use]
-extern crate dotenv_codegen;
-
 use std::error::Error;
-use std::fmt::Display;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
 use clap::{Parser, Subcommand};
+use emoji_kitchen::{animation, emoji, process, query, skin_tone, template, write};
 
 mod animations;
+mod browse;
+mod config;
 mod discord;
-mod emoji;
+mod download;
+mod grid;
+mod mix;
+mod skin_tones;
+mod sprites;
 mod upload;
-mod utils;
-mod write;
+mod validate;
 
 #[derive(Parser)]
 #[command(version, about)]
 struct Cli {
     #[command(subcommand)]
     command: Option<Commands>,
+    /// pairs.txt to load instead of the data dir or embedded copy
+    #[arg(long, global = true)]
+    pairs_file: Option<PathBuf>,
+    /// emoji.json to load instead of the data dir or embedded copy
+    #[arg(long, global = true)]
+    emoji_data: Option<PathBuf>,
+    /// config.toml to load instead of the one in the config dir
+    #[arg(long, global = true)]
+    config: Option<PathBuf>,
+    /// config profile to use for Slack and Discord credentials
+    #[arg(long, global = true)]
+    profile: Option<String>,
+    /// fail on invalid lines in pairs.txt instead of skipping them
+    #[arg(long, global = true)]
+    strict: bool,
+    /// which date revisions to use when a pair has more than one
+    #[arg(long, global = true, value_enum, default_value_t)]
+    revisions: emoji::Revisions,
+    /// prefer pairs with this skin tone, e.g. medium-dark or 5
+    #[arg(long, global = true)]
+    skin_tone: Option<skin_tone::SkinTone>,
 }
 
 #[derive(Subcommand)]
@@ -28,22 +52,123 @@ enum Commands {
         name: Option<String>,
         #[arg(short, long)]
         o: String,
-        #[arg(short, long)]
-        size: u32,
+        /// width and height in pixels; not for lottie-json
+        #[arg(short, long, value_parser = clap::value_parser!(u32).range(1..))]
+        size: Option<u32>,
+        /// webp and apng have full alpha; lottie-json is saved as is, so
+        /// --size, --budget, --fps and --speed don't apply
+        #[arg(short, long, value_enum, default_value_t)]
+        format: animation::Format,
+        /// resampling filter
+        #[arg(long, value_enum, default_value_t)]
+        filter: animation::Filter,
+        /// how animations that aren't square are scaled
+        #[arg(long, value_enum, default_value_t)]
+        aspect: animation::Aspect,
+        /// largest file size, e.g. 256K for Discord or 128K for Slack; colors,
+        /// frames and size are reduced until each animation fits
+        #[arg(long, value_parser = parse_byte_size)]
+        budget: Option<u64>,
+        /// show every frame for 1/fps seconds, instead of its original delay
+        #[arg(long, conflicts_with = "speed")]
+        fps: Option<f64>,
+        /// play the animation this many times as fast, e.g. 0.5 for half speed
+        #[arg(long)]
+        speed: Option<f64>,
+        /// read the catalog (api.json) from this file instead
+        #[arg(long)]
+        catalog_file: Option<PathBuf>,
+        /// use the cached catalog, however old, instead of fetching it
+        #[arg(long)]
+        offline: bool,
+        /// how long the cached catalog is used before fetching it again
+        #[arg(long, value_parser = humantime::parse_duration, default_value = "1day")]
+        catalog_ttl: Duration,
     },
-    Discord {
+    /// Browse emoji and their combinations in a full-screen terminal UI
+    Browse {
+        /// where downloaded images are, and where `d` saves them
+        #[arg(short, long, default_value = "dist")]
+        input: PathBuf,
         #[arg(short, long)]
         name: Option<String>,
+        /// filter pairs, e.g. 'base:cat AND category:"Food & Drink"'
+        #[arg(short, long)]
+        query: Option<query::Query>,
+        /// where `u` queues images for `upload --pairs`
+        #[arg(long, default_value = "upload-queue")]
+        queue: PathBuf,
     },
+    Discord,
     Download {
         #[arg(short, long)]
         name: Option<String>,
+        /// filter pairs, e.g. 'base:cat AND category:"Food & Drink"'
+        #[arg(short, long)]
+        query: Option<query::Query>,
+        #[arg(short, long, default_value = "dist")]
+        output: PathBuf,
+        /// output path of each image, without extension, e.g. "{base.short_name}/{name}"
+        #[arg(long, default_value = template::DEFAULT_TEMPLATE)]
+        template: template::FilenameTemplate,
+        /// subdirectories to sort images into
+        #[arg(long, value_enum, default_value_t)]
+        layout: template::Layout,
+        /// number of images to download at once
+        #[arg(long, default_value_t = 8)]
+        concurrency: usize,
+        /// download everything, ignoring <output>/manifest.json
+        #[arg(long)]
+        force: bool,
+        /// hours before a downloaded image is revalidated with the server
+        #[arg(long, default_value_t = 24 * 7)]
+        max_age: u64,
+        /// check downloaded images against their sha-256, not just their size
+        #[arg(long)]
+        verify: bool,
+        /// also save copies scaled to fit these sizes, e.g. 128,64
+        #[arg(long, value_delimiter = ',', value_parser = clap::value_parser!(u32).range(1..))]
+        resize: Vec<u32>,
+        /// also save copies in these formats, e.g. webp,avif
+        #[arg(long, value_enum, value_delimiter = ',')]
+        convert: Vec<process::ImageFormat>,
+        /// crop the transparent padding off the copies
+        #[arg(long)]
+        trim: bool,
+        /// losslessly recompress PNG copies
+        #[arg(long)]
+        optimize: bool,
     },
     Json {
         #[arg(short, long)]
         name: Option<String>,
+        /// filter pairs, e.g. 'base:cat AND category:"Food & Drink"'
+        #[arg(short, long)]
+        query: Option<query::Query>,
+        #[arg(short, long, required_unless_present = "export_schema")]
+        output: Option<String>,
+        #[arg(short, long, value_enum, default_value_t)]
+        format: write::Format,
+        /// drop mirrored pairs, e.g. pizza_cat when cat_pizza exists
+        #[arg(long)]
+        dedupe_mirrored: bool,
+        /// print the JSON Schema of the export and exit
+        #[arg(long)]
+        export_schema: bool,
+        /// with `--format indexed`, write `index.json` and one file per base
+        /// into the `--output` directory
+        #[arg(long)]
+        shards: bool,
+    },
+    /// Find the combination of two emoji, e.g. `mix 🐱 🍕`
+    Mix {
+        a: String,
+        b: String,
+        /// save the image to this file or directory
         #[arg(short, long)]
-        output: String,
+        output: Option<String>,
+        #[arg(short, long)]
+        preview: bool,
     },
     Show {
         #[arg(short, long)]
@@ -52,8 +177,55 @@ enum Commands {
         input: String,
         #[arg(short, long)]
         name: Option<String>,
+        /// filter pairs, e.g. 'base:cat AND category:"Food & Drink"'
+        #[arg(short, long)]
+        query: Option<query::Query>,
         #[arg(short, long)]
         preview: bool,
+        /// tile the previews in rows across the terminal
+        #[arg(short, long)]
+        grid: bool,
+        /// previews per row for --grid and --sheet, instead of fitting the terminal
+        #[arg(long)]
+        columns: Option<u32>,
+        /// write the previews to this PNG as a labeled contact sheet
+        #[arg(long)]
+        sheet: Option<PathBuf>,
+        /// drop mirrored pairs, e.g. pizza_cat when cat_pizza exists
+        #[arg(long)]
+        dedupe_mirrored: bool,
+    },
+    /// List the skin tone variants of an emoji and which have kitchen artwork
+    SkinTones {
+        emoji: String,
+    },
+    /// Pack downloaded images into sprite sheets with a JSON atlas
+    Sprites {
+        /// the download directory to read images from
+        #[arg(short, long, default_value = "dist")]
+        input: PathBuf,
+        #[arg(short, long, default_value = "sprites")]
+        output: PathBuf,
+        #[arg(short, long)]
+        name: Option<String>,
+        /// filter pairs, e.g. 'base:cat AND category:"Food & Drink"'
+        #[arg(short, long)]
+        query: Option<query::Query>,
+        /// width and height of each sprite, in pixels
+        #[arg(long, default_value_t = 64)]
+        cell_size: u32,
+        /// largest width and height of a sheet, in pixels
+        #[arg(long, default_value_t = 4096)]
+        max_size: u32,
+        /// filename of the atlas and sheets, without extension
+        #[arg(long, default_value = "sprites")]
+        atlas_name: String,
+        /// also write CSS classes, named <prefix>-<pair name>
+        #[arg(long, value_name = "PREFIX", num_args = 0..=1, default_missing_value = "ek")]
+        css: Option<String>,
+        /// drop mirrored pairs, e.g. pizza_cat when cat_pizza exists
+        #[arg(long)]
+        dedupe_mirrored: bool,
     },
     ShowAnimated {
         #[arg(short, long)]
@@ -64,58 +236,367 @@ enum Commands {
         input: String,
         #[arg(short, long)]
         name: Option<String>,
+        /// filter pairs, e.g. 'base:cat AND category:"Food & Drink"'
+        #[arg(short, long)]
+        query: Option<query::Query>,
+        /// upload pair images named after the pair, e.g. the `browse` queue
+        #[arg(long)]
+        pairs: bool,
     },
+    /// Report unresolved codepoints, invalid date codes and duplicate lines in pairs.txt
+    Validate,
 }
 
 #[tokio::main]
 async fn main() {
     let cli = Cli::parse();
+    let sources = sources(&cli);
 
     match &cli.command {
-        Some(Commands::Animations { name, o, size }) => {
-            animations::animations(Path::new(o), name, size).await;
+        Some(Commands::Animations {
+            name,
+            o,
+            size,
+            format,
+            filter,
+            aspect,
+            budget,
+            fps,
+            speed,
+            catalog_file,
+            offline,
+            catalog_ttl,
+        }) => {
+            if *format == animation::Format::LottieJson
+                && (size.is_some() || budget.is_some() || fps.is_some() || speed.is_some())
+            {
+                println!("🚫 lottie-json is saved as is, so --size, --budget, --fps and --speed don't apply");
+                std::process::exit(1);
+            }
+            let size = match (size, format) {
+                (Some(size), _) => *size,
+                // unused, lottie-json isn't resized
+                (None, animation::Format::LottieJson) => 0,
+                (None, _) => {
+                    println!("🚫 --size is needed, except with --format lottie-json");
+                    std::process::exit(1);
+                }
+            };
+            if fps.or(*speed).is_some_and(|value| value <= 0.0) {
+                println!("🚫 --fps and --speed must be more than 0");
+                std::process::exit(1);
+            }
+            let retime = match (fps, speed) {
+                (Some(fps), _) => Some(animation::Retime::Fps(*fps)),
+                (_, Some(speed)) => Some(animation::Retime::Speed(*speed)),
+                _ => None,
+            };
+            let options = animation::Options {
+                format: *format,
+                size,
+                filter: *filter,
+                aspect: *aspect,
+                retime,
+                budget: *budget,
+            };
+            let catalog = animations::Catalog {
+                file: catalog_file.clone(),
+                offline: *offline,
+                ttl: *catalog_ttl,
+            };
+            animations::animations(Path::new(o), name, &catalog, options).await;
+        }
+        Some(Commands::Browse {
+            input,
+            name,
+            query,
+            queue,
+        }) => {
+            let options = db_options(&cli, query::Query::from_options(name, query));
+            let emoji = init_or_exit(options);
+            let browse_options = browse::Options {
+                input: input.clone(),
+                queue: queue.clone(),
+            };
+            if let Err(err) = browse::browse(&emoji, browse_options).await {
+                println!("🚫 {}", err);
+                std::process::exit(1);
+            }
         }
-        Some(Commands::Discord { name }) => {
-            discord::discord(name).await;
+        Some(Commands::Discord) => {
+            let config = config_or_exit(load_config(&cli).and_then(|c| c.discord()));
+            if let Err(err) = discord::discord(&config).await {
+                println!("🚫 {}", err);
+            }
         }
-        Some(Commands::Download { name }) => {
-            let options = emoji::Options { name: name.clone() };
-            let emoji = emoji::init(options);
-            download(emoji.pairs).await;
+        Some(Commands::Download {
+            name,
+            query,
+            output,
+            template,
+            layout,
+            concurrency,
+            force,
+            max_age,
+            verify,
+            resize,
+            convert,
+            trim,
+            optimize,
+        }) => {
+            let options = db_options(&cli, query::Query::from_options(name, query));
+            let emoji = init_or_exit(options);
+            let download_options = download::Options {
+                output: output.clone(),
+                template: template.clone().with_layout(*layout),
+                concurrency: *concurrency,
+                force: *force,
+                max_age_secs: max_age.saturating_mul(60 * 60),
+                verify: *verify,
+                pipeline: process::Pipeline {
+                    sizes: resize.clone(),
+                    formats: convert.clone(),
+                    trim: *trim,
+                    optimize: *optimize,
+                },
+            };
+            download::download(emoji.into_pairs(), download_options).await;
         }
-        Some(Commands::Json { name, output }) => {
+        Some(Commands::Json {
+            name,
+            query,
+            output,
+            format,
+            dedupe_mirrored,
+            export_schema,
+            shards,
+        }) => {
+            if *export_schema {
+                println!("{}", write::json_schema());
+                return;
+            }
+            let output = output.as_deref().unwrap_or_default();
+            if *shards && *format != write::Format::Indexed {
+                println!("🚫 --shards needs --format indexed");
+                std::process::exit(1);
+            }
             let now = std::time::SystemTime::now();
             println!("{:#?}", &now);
-            let options = emoji::Options { name: name.clone() };
-            let emoji = emoji::init(options);
-            write::json(emoji.pairs, Path::new(output)).await;
+            let options = db_options(&cli, query::Query::from_options(name, query));
+            let mut emoji = init_or_exit(options);
+            if *dedupe_mirrored {
+                emoji.dedupe_mirrored();
+            }
+            let result = if *shards {
+                write::indexed_shards(&emoji, Path::new(output)).await
+            } else {
+                write::export(&emoji, Path::new(output), *format).await
+            };
+            if let Err(err) = result {
+                println!("🚫 Failed to write {}: {}", output, err);
+            }
             println!("{:#?}", now.elapsed().unwrap());
         }
+        Some(Commands::Mix {
+            a,
+            b,
+            output,
+            preview,
+        }) => {
+            let options = db_options(&cli, None);
+            let emoji = init_or_exit(options);
+            if let Err(err) = mix::mix(&emoji, a, b, cli.skin_tone, output, preview).await {
+                println!("🚫 {}", err);
+                std::process::exit(1);
+            }
+        }
         Some(Commands::Show {
             count,
             input,
             name,
+            query,
             preview,
+            grid,
+            columns,
+            sheet,
+            dedupe_mirrored,
         }) => {
-            let options = emoji::Options { name: name.clone() };
-            let emoji = emoji::init(options);
-            show(emoji.pairs, Path::new(input), count, preview);
+            let options = db_options(&cli, query::Query::from_options(name, query));
+            let mut emoji = init_or_exit(options);
+            if *dedupe_mirrored {
+                emoji.dedupe_mirrored();
+            }
+            let input = Path::new(input);
+            let columns = columns.unwrap_or_else(grid::columns);
+            if let Some(sheet) = sheet {
+                match grid::write_sheet(emoji.pairs(), input, columns, sheet) {
+                    Err(err) => println!("🚫 Failed to write {}: {}", sheet.display(), err),
+                    Ok(pages) if pages.is_empty() => println!(
+                        "⚠️ No downloaded images of the matching pairs in {}",
+                        input.display()
+                    ),
+                    Ok(pages) => {
+                        for page in pages {
+                            println!("✅ {}", page.display());
+                        }
+                    }
+                }
+            } else if *grid && !*count {
+                grid::print_grid(emoji.pairs(), input, columns);
+            } else {
+                show(emoji.into_pairs(), input, count, preview);
+            }
+        }
+        Some(Commands::SkinTones { emoji }) => {
+            let options = emoji::Options {
+                skin_tone: None,
+                ..db_options(&cli, None)
+            };
+            let db = init_or_exit(options);
+            if let Err(err) = skin_tones::skin_tones(&db, emoji) {
+                println!("🚫 {}", err);
+                std::process::exit(1);
+            }
+        }
+        Some(Commands::Sprites {
+            input,
+            output,
+            name,
+            query,
+            cell_size,
+            max_size,
+            atlas_name,
+            css,
+            dedupe_mirrored,
+        }) => {
+            let options = db_options(&cli, query::Query::from_options(name, query));
+            let mut emoji = init_or_exit(options);
+            if *dedupe_mirrored {
+                emoji.dedupe_mirrored();
+            }
+            let sprites_options = sprites::Options {
+                input: input.clone(),
+                output: output.clone(),
+                cell_size: (*cell_size).max(1),
+                max_size: *max_size,
+                name: atlas_name.clone(),
+                css_prefix: css.clone(),
+            };
+            if let Err(err) = sprites::sprites(emoji.pairs(), sprites_options) {
+                println!("🚫 {}", err);
+                std::process::exit(1);
+            }
         }
         Some(Commands::ShowAnimated { input }) => {
-            show_animated(Path::new(input));
+            show_animated(Path::new(input), &sources);
         }
-        Some(Commands::Upload { input, name }) => {
-            upload::upload(Path::new(input), name).await;
+        Some(Commands::Upload {
+            input,
+            name,
+            query,
+            pairs,
+        }) => {
+            let config = config_or_exit(load_config(&cli).and_then(|c| c.slack()));
+            let query = query::Query::from_options(name, query);
+            if *pairs {
+                let options = db_options(&cli, query);
+                let emoji = init_or_exit(options);
+                upload::upload_pairs(&config, Path::new(input), emoji.pairs()).await;
+            } else {
+                upload::upload(&config, Path::new(input), &query, &sources).await;
+            }
         }
+        Some(Commands::Validate) => match validate::validate(&sources) {
+            Err(err) => {
+                println!("🚫 Failed to load emoji data: {}", err);
+                std::process::exit(1);
+            }
+            Ok(0) => {}
+            Ok(_) => std::process::exit(1),
+        },
         None => {
             println!("you fucked up. specify a command. TODO: print help output");
         }
     }
 }
 
-fn show_animated(input_path: &Path) {
+fn load_config(cli: &Cli) -> Result<config::Config, Box<dyn Error>> {
+    config::Config::load(&cli.config, &cli.profile)
+}
+
+fn sources(cli: &Cli) -> emoji::Sources {
+    emoji::Sources {
+        pairs_file: cli.pairs_file.clone(),
+        emoji_data: cli.emoji_data.clone(),
+    }
+}
+
+/// Options for loading the db, from the global flags.
+fn db_options(cli: &Cli, query: Option<query::Query>) -> emoji::Options {
+    emoji::Options {
+        query,
+        sources: sources(cli),
+        revisions: cli.revisions,
+        strict: cli.strict,
+        skin_tone: cli.skin_tone,
+    }
+}
+
+fn config_or_exit<T>(result: Result<T, Box<dyn Error>>) -> T {
+    match result {
+        Err(err) => {
+            println!("🚫 Invalid configuration: {}", err);
+            std::process::exit(1);
+        }
+        Ok(config) => config,
+    }
+}
+
+/// Parses a byte count with an optional `K`/`KB` or `M`/`MB` suffix (1024
+/// based), e.g. `256K`.
+fn parse_byte_size(s: &str) -> Result<u64, Box<dyn Error + Send + Sync>> {
+    let upper = s.trim().to_ascii_uppercase();
+    let number = upper.trim_end_matches('B');
+    let (number, multiplier) = if let Some(number) = number.strip_suffix('K') {
+        (number, 1024)
+    } else if let Some(number) = number.strip_suffix('M') {
+        (number, 1024 * 1024)
+    } else {
+        (number, 1)
+    };
+    match number.trim().parse::<u64>() {
+        Ok(bytes) if bytes > 0 => Ok(bytes * multiplier),
+        _ => Err(format!("invalid size: {}", s).into()),
+    }
+}
+
+fn init_or_exit(options: emoji::Options) -> emoji::EmojiDB {
+    match emoji::init(options) {
+        Err(err) => {
+            println!("🚫 Failed to load emoji data: {}", err);
+            std::process::exit(1);
+        }
+        Ok(emoji) => {
+            if !emoji.errors.is_empty() {
+                eprintln!(
+                    "⚠️ skipped {} invalid lines in pairs.txt (run `validate` for details)",
+                    emoji.errors.len()
+                );
+            }
+            emoji
+        }
+    }
+}
+
+fn show_animated(input_path: &Path, sources: &emoji::Sources) {
     let paths = std::fs::read_dir(input_path).unwrap();
-    let emoji_map = emoji::get_emoji_map();
+    let emoji_map = match emoji::get_emoji_map(sources) {
+        Err(err) => {
+            println!("🚫 Failed to load emoji data: {}", err);
+            return;
+        }
+        Ok(map) => map,
+    };
 
     let mut emoji_list = Vec::new();
 
@@ -139,7 +620,7 @@ fn show_animated(input_path: &Path) {
         ));
     }
 
-    emoji_list.sort_by(|a, b| a.0.cmp(&b.0));
+    emoji_list.sort_by_key(|a| a.0);
 
     for (_, name) in emoji_list {
         print!("{}", &name);
@@ -160,37 +641,9 @@ fn show(pairs: Vec<emoji::EmojiPair>, input_path: &Path, count: &bool, preview:
                     width: Some(16),
                     ..Default::default()
                 };
-                match viuer::print_from_file(filename, &config) {
-                    Err(_) => {}
-                    Ok(_) => {}
-                }
+                // a missing or unreadable image just means no preview
+                let _ = viuer::print_from_file(filename, &config);
             }
         }
     }
 }
-
-async fn download(pairs: Vec<emoji::EmojiPair>) {
-    println!("{} pairs found", pairs.len());
-
-    let mut errors: Vec<(Box<dyn Display>, Box<dyn Error>)> = Vec::new();
-
-    for pair in &pairs {
-        let dest_path = Path::new("dist").join(&pair.filename);
-        match utils::download_and_save_image(&pair.image_url, &dest_path).await {
-            Err(err) => {
-                println!("🚫 {} {} {}", pair.filename, pair.image_url, err);
-                errors.push((
-                    Box::new(format!("{} {}", pair.filename, pair.image_url)),
-                    err,
-                ));
-            }
-            Ok(_) => println!("✅ {} {}", pair.filename, pair.image_url),
-        }
-    }
-
-    println!("ℹ️ Completed with {} errors", errors.len());
-
-    for (message, error) in errors {
-        println!("🚫 {} {}", message, error);
-    }
-}
@@ -0,0 +1,197 @@
+use std::collections::{BTreeMap, HashMap};
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+/// What was downloaded for a single file, used to skip or revalidate it on the
+/// next run.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ManifestEntry {
+    pub url: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub etag: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_modified: Option<String>,
+    pub size: u64,
+    pub sha256: String,
+    /// seconds since the unix epoch when the file was last downloaded or revalidated
+    pub timestamp: u64,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Freshness {
+    /// downloaded recently and still on disk, no request needed
+    Fresh,
+    /// on disk, but old enough that it should be revalidated with a conditional request
+    Stale,
+    /// not downloaded, changed url, or the file on disk doesn't match
+    Missing,
+}
+
+impl ManifestEntry {
+    /// With `verify`, a file of the right size is also hashed, to catch one
+    /// that was corrupted in place.
+    pub fn freshness(&self, url: &str, path: &Path, max_age_secs: u64, verify: bool) -> Freshness {
+        if self.url != url {
+            return Freshness::Missing;
+        }
+        match fs::metadata(path) {
+            Ok(metadata) if metadata.len() == self.size => {
+                if verify && !self.matches_hash(path) {
+                    return Freshness::Missing;
+                }
+                if now().saturating_sub(self.timestamp) <= max_age_secs {
+                    Freshness::Fresh
+                } else {
+                    Freshness::Stale
+                }
+            }
+            _ => Freshness::Missing,
+        }
+    }
+
+    fn matches_hash(&self, path: &Path) -> bool {
+        fs::read(path).is_ok_and(|bytes| format!("{:x}", Sha256::digest(bytes)) == self.sha256)
+    }
+}
+
+/// `manifest.json` in a download directory, keyed by filename.
+#[derive(Serialize, Deserialize, Default, Debug)]
+pub struct Manifest {
+    pub files: BTreeMap<String, ManifestEntry>,
+}
+
+impl Manifest {
+    const FILENAME: &str = "manifest.json";
+
+    pub fn path(dir: &Path) -> PathBuf {
+        dir.join(Manifest::FILENAME)
+    }
+
+    /// A missing manifest is treated as empty.
+    pub fn load(dir: &Path) -> Result<Manifest, Box<dyn Error>> {
+        let path = Manifest::path(dir);
+        match fs::read(&path) {
+            Ok(bytes) => serde_json::from_slice(&bytes)
+                .map_err(|err| format!("could not parse {}: {}", path.display(), err).into()),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(Manifest::default()),
+            Err(err) => Err(format!("could not read {}: {}", path.display(), err).into()),
+        }
+    }
+
+    /// The filename each url was saved under, to find a pair's image whatever
+    /// template it was downloaded with.
+    pub fn filenames_by_url(&self) -> HashMap<String, String> {
+        self.files
+            .iter()
+            .map(|(filename, entry)| (entry.url.to_owned(), filename.to_owned()))
+            .collect()
+    }
+
+    /// Writes to a temporary file first, so an interrupted save can't corrupt
+    /// the existing manifest.
+    pub fn save(&self, dir: &Path) -> Result<(), Box<dyn Error>> {
+        let path = Manifest::path(dir);
+        let tmp_path = path.with_extension("json.tmp");
+        fs::write(&tmp_path, serde_json::to_string_pretty(self)?)?;
+        fs::rename(&tmp_path, &path)?;
+        Ok(())
+    }
+}
+
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const URL: &str = "https://example.com/cat_pizza.png";
+
+    /// A file with `bytes` in the temp dir, removed on drop.
+    struct TempFile(PathBuf);
+
+    impl TempFile {
+        fn new(name: &str, bytes: &[u8]) -> Self {
+            let path = std::env::temp_dir().join(format!(
+                "{}-{}-{}",
+                env!("CARGO_PKG_NAME"),
+                std::process::id(),
+                name
+            ));
+            fs::write(&path, bytes).unwrap();
+            TempFile(path)
+        }
+    }
+
+    impl Drop for TempFile {
+        fn drop(&mut self) {
+            let _ = fs::remove_file(&self.0);
+        }
+    }
+
+    fn entry(bytes: &[u8], age_secs: u64) -> ManifestEntry {
+        ManifestEntry {
+            url: URL.to_owned(),
+            etag: None,
+            last_modified: None,
+            size: bytes.len() as u64,
+            sha256: format!("{:x}", Sha256::digest(bytes)),
+            timestamp: now() - age_secs,
+        }
+    }
+
+    #[test]
+    fn recent_downloads_are_fresh_and_old_ones_stale() {
+        let file = TempFile::new("age.png", b"image");
+        let entry = entry(b"image", 7200);
+        assert_eq!(entry.freshness(URL, &file.0, 3600, false), Freshness::Stale);
+        assert_eq!(
+            entry.freshness(URL, &file.0, 10800, false),
+            Freshness::Fresh
+        );
+        assert_eq!(
+            entry.freshness(URL, &file.0, u64::MAX, true),
+            Freshness::Fresh
+        );
+    }
+
+    #[test]
+    fn changed_urls_and_files_are_missing() {
+        let file = TempFile::new("changed.png", b"image");
+        let entry = entry(b"image", 0);
+        let other = "https://example.com/pizza_cat.png";
+        assert_eq!(
+            entry.freshness(other, &file.0, 3600, false),
+            Freshness::Missing
+        );
+
+        let resized = TempFile::new("resized.png", b"larger image");
+        assert_eq!(
+            entry.freshness(URL, &resized.0, 3600, false),
+            Freshness::Missing
+        );
+
+        let gone = TempFile::new("gone.png", b"image").0.clone();
+        assert_eq!(entry.freshness(URL, &gone, 3600, false), Freshness::Missing);
+    }
+
+    #[test]
+    fn verify_catches_same_size_corruption() {
+        let file = TempFile::new("corrupt.png", b"imagf");
+        let entry = entry(b"image", 0);
+        assert_eq!(entry.freshness(URL, &file.0, 3600, false), Freshness::Fresh);
+        assert_eq!(
+            entry.freshness(URL, &file.0, 3600, true),
+            Freshness::Missing
+        );
+    }
+}
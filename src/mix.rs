@@ -0,0 +1,116 @@
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+use emoji_kitchen::emoji::{self, Emoji, EmojiDB};
+use emoji_kitchen::skin_tone::SkinTone;
+use emoji_kitchen::utils;
+
+// how many partners to suggest when a combination doesn't exist
+const SUGGESTIONS: usize = 10;
+
+/// Partners of `emoji` in the db, closest to `target` in sort order first.
+fn nearest_partners<'a>(db: &'a EmojiDB, emoji: &Emoji, target: &Emoji) -> Vec<&'a Emoji> {
+    let mut partners: Vec<&Emoji> = db
+        .pairs()
+        .iter()
+        .filter_map(|pair| {
+            if pair.base.codepoint == emoji.codepoint {
+                Some(&pair.pair)
+            } else if pair.pair.codepoint == emoji.codepoint {
+                Some(&pair.base)
+            } else {
+                None
+            }
+        })
+        .collect();
+    partners.sort_by_key(|partner| {
+        (
+            partner.sort_order.abs_diff(target.sort_order),
+            partner.sort_order,
+        )
+    });
+    partners.dedup_by(|a, b| a.codepoint == b.codepoint);
+    partners.truncate(SUGGESTIONS);
+    partners
+}
+
+fn resolve<'a>(db: &'a EmojiDB, input: &str) -> Result<&'a Emoji, Box<dyn Error>> {
+    emoji::find_emoji(&db.emoji_map, input)
+        .ok_or_else(|| Box::<dyn Error>::from(format!("no emoji named {:?}", input)))
+}
+
+fn format_partners(partners: &[&Emoji]) -> String {
+    if partners.is_empty() {
+        return "nothing".to_owned();
+    }
+    partners
+        .iter()
+        .map(|partner| partner.short_name.as_str())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+pub async fn mix(
+    db: &EmojiDB,
+    a: &str,
+    b: &str,
+    skin_tone: Option<SkinTone>,
+    output: &Option<String>,
+    preview: &bool,
+) -> Result<(), Box<dyn Error>> {
+    let a = resolve(db, a)?;
+    let b = resolve(db, b)?;
+
+    let pair = match db.get_with_skin_tone(a, b, skin_tone) {
+        Some(pair) => pair,
+        None => {
+            return Err(Box::<dyn Error>::from(format!(
+                "no such combination: {} + {}\n  {} pairs with: {}\n  {} pairs with: {}",
+                a.short_name,
+                b.short_name,
+                a.short_name,
+                format_partners(&nearest_partners(db, a, b)),
+                b.short_name,
+                format_partners(&nearest_partners(db, b, a)),
+            )));
+        }
+    };
+
+    println!("{}", pair.name);
+    println!("{}", pair.image_url);
+
+    for older in db.revisions(&pair.base, &pair.pair).iter().skip(1) {
+        println!("  {} {}", older.name, older.image_url);
+    }
+
+    if output.is_none() && !preview {
+        return Ok(());
+    }
+    // downloaded once for both saving and previewing
+    let bytes = utils::fetch_bytes(&utils::retrying_client(), &pair.image_url).await?;
+
+    if let Some(output) = output {
+        let output = Path::new(output);
+        let dest_path = if output.is_dir() {
+            output.join(&pair.filename)
+        } else {
+            output.to_path_buf()
+        };
+        fs::write(&dest_path, &bytes)?;
+        println!("✅ {}", dest_path.display());
+    }
+
+    if *preview {
+        let image = image::load_from_memory(&bytes)?;
+        let config = viuer::Config {
+            transparent: true,
+            absolute_offset: false,
+            width: Some(32),
+            ..Default::default()
+        };
+        viuer::print(&image, &config)?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,205 @@
+use std::error::Error;
+use std::fmt;
+use std::io::Cursor;
+
+use image::codecs::gif::GifEncoder;
+use image::codecs::png::PngEncoder;
+use image::{imageops, DynamicImage, GenericImageView, ImageEncoder, RgbaImage};
+
+/// What a processed image is encoded as.
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ImageFormat {
+    Png,
+    /// lossless
+    Webp,
+    /// a single frame, with a 256 color palette
+    Gif,
+    /// needs the `avif` feature
+    Avif,
+}
+
+impl ImageFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ImageFormat::Png => "png",
+            ImageFormat::Webp => "webp",
+            ImageFormat::Gif => "gif",
+            ImageFormat::Avif => "avif",
+        }
+    }
+}
+
+impl fmt::Display for ImageFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.extension())
+    }
+}
+
+/// One output of a `Pipeline`: the image scaled to `size` and encoded as
+/// `format`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Variant {
+    /// largest width and height, in pixels; `None` keeps the original size
+    pub size: Option<u32>,
+    pub format: ImageFormat,
+}
+
+impl Variant {
+    /// The folder the variant is written to, e.g. `128-webp`, or
+    /// `original-png` when it isn't resized.
+    pub fn dir_name(&self) -> String {
+        match self.size {
+            Some(size) => format!("{}-{}", size, self.format),
+            None => format!("original-{}", self.format),
+        }
+    }
+}
+
+/// The steps applied to each downloaded image: trim, then resize to each of
+/// `sizes` and encode each as every one of `formats`.
+#[derive(Clone, Debug, Default)]
+pub struct Pipeline {
+    /// largest width and height of each variant, in pixels; the original
+    /// size when empty
+    pub sizes: Vec<u32>,
+    /// PNG when empty
+    pub formats: Vec<ImageFormat>,
+    /// crop fully transparent rows and columns off the edges first
+    pub trim: bool,
+    /// recompress PNGs losslessly, which needs the `optimize` feature
+    pub optimize: bool,
+}
+
+impl Pipeline {
+    /// Whether there's nothing to do, so the downloads are all there is.
+    pub fn is_empty(&self) -> bool {
+        self.sizes.is_empty() && self.formats.is_empty() && !self.trim && !self.optimize
+    }
+
+    pub fn variants(&self) -> Vec<Variant> {
+        let sizes: Vec<Option<u32>> = if self.sizes.is_empty() {
+            vec![None]
+        } else {
+            self.sizes.iter().copied().map(Some).collect()
+        };
+        let formats = if self.formats.is_empty() {
+            vec![ImageFormat::Png]
+        } else {
+            self.formats.clone()
+        };
+        sizes
+            .iter()
+            .flat_map(|&size| formats.iter().map(move |&format| Variant { size, format }))
+            .collect()
+    }
+
+    /// Trims and resizes `image` for `variant`, returning the encoded file.
+    pub fn render(
+        &self,
+        image: &DynamicImage,
+        variant: &Variant,
+    ) -> Result<Vec<u8>, Box<dyn Error>> {
+        let trimmed;
+        let image = if self.trim {
+            trimmed = trim(image);
+            &trimmed
+        } else {
+            image
+        };
+        let image = match variant.size {
+            Some(size) => image.resize(size, size, imageops::FilterType::Lanczos3),
+            None => image.clone(),
+        };
+        encode(&image.to_rgba8(), variant.format, self.optimize)
+    }
+}
+
+/// `image` without the fully transparent rows and columns around it. A fully
+/// transparent image is returned as is.
+pub fn trim(image: &DynamicImage) -> DynamicImage {
+    let (width, height) = image.dimensions();
+    let (mut left, mut top, mut right, mut bottom) = (width, height, 0, 0);
+    for (x, y, pixel) in image.pixels() {
+        if pixel[3] == 0 {
+            continue;
+        }
+        left = left.min(x);
+        top = top.min(y);
+        right = right.max(x + 1);
+        bottom = bottom.max(y + 1);
+    }
+    if left >= right || top >= bottom {
+        return image.clone();
+    }
+    image.crop_imm(left, top, right - left, bottom - top)
+}
+
+/// Encodes `image` as `format`. `optimize` recompresses PNGs with oxipng.
+pub fn encode(
+    image: &RgbaImage,
+    format: ImageFormat,
+    optimize: bool,
+) -> Result<Vec<u8>, Box<dyn Error>> {
+    let mut bytes = Vec::new();
+    let (width, height) = image.dimensions();
+    match format {
+        ImageFormat::Png => {
+            PngEncoder::new(&mut bytes).write_image(
+                image.as_raw(),
+                width,
+                height,
+                image::ColorType::Rgba8,
+            )?;
+            if optimize {
+                bytes = optimize_png(&bytes)?;
+            }
+        }
+        ImageFormat::Webp => {
+            bytes = webp::Encoder::from_rgba(image.as_raw(), width, height)
+                .encode_lossless()
+                .to_vec();
+        }
+        ImageFormat::Gif => {
+            let mut encoder = GifEncoder::new(Cursor::new(&mut bytes));
+            encoder.encode(image.as_raw(), width, height, image::ColorType::Rgba8)?;
+        }
+        ImageFormat::Avif => bytes = avif(image)?,
+    }
+    Ok(bytes)
+}
+
+#[cfg(feature = "optimize")]
+fn optimize_png(png: &[u8]) -> Result<Vec<u8>, Box<dyn Error>> {
+    Ok(oxipng::optimize_from_memory(
+        png,
+        &oxipng::Options::from_preset(2),
+    )?)
+}
+
+#[cfg(not(feature = "optimize"))]
+fn optimize_png(_: &[u8]) -> Result<Vec<u8>, Box<dyn Error>> {
+    Err("optimizing PNGs needs the `optimize` feature".into())
+}
+
+#[cfg(feature = "avif")]
+fn avif(image: &RgbaImage) -> Result<Vec<u8>, Box<dyn Error>> {
+    let pixels: Vec<ravif::RGBA8> = image
+        .pixels()
+        .map(|p| ravif::RGBA8::new(p[0], p[1], p[2], p[3]))
+        .collect();
+    let encoded = ravif::Encoder::new()
+        .with_quality(80.)
+        .with_speed(6)
+        .encode_rgba(ravif::Img::new(
+            pixels.as_slice(),
+            image.width() as usize,
+            image.height() as usize,
+        ))?;
+    Ok(encoded.avif_file)
+}
+
+#[cfg(not(feature = "avif"))]
+fn avif(_: &RgbaImage) -> Result<Vec<u8>, Box<dyn Error>> {
+    Err("AVIF output needs the `avif` feature".into())
+}
@@ -0,0 +1,571 @@
+use std::error::Error;
+use std::str::FromStr;
+
+use regex::{Regex, RegexBuilder};
+
+use crate::emoji::{Emoji, EmojiPair};
+
+type ParseError = Box<dyn Error + Send + Sync>;
+
+/// Which emoji of a pair a term is checked against.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Position {
+    Either,
+    Base,
+    Pair,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Field {
+    /// `short_name` or any alias, text shortcut, emoji character or codepoint
+    Names,
+    ShortName,
+    Name,
+    Codepoint,
+    Category,
+    Subcategory,
+    /// the raw hex date code from `pairs.txt`
+    D,
+    /// the decoded `YYYYMMDD` date
+    Date,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Comparison {
+    Lt,
+    Le,
+    Eq,
+    Ge,
+    Gt,
+}
+
+#[derive(Clone, Debug)]
+enum Pattern {
+    /// case-insensitive exact match
+    Exact(String),
+    /// `/.../` regexes and `*`/`?` globs
+    Regex(Regex),
+    Compare(Comparison, i64),
+}
+
+impl Pattern {
+    fn parse(value: &str) -> Result<Self, ParseError> {
+        if value.len() >= 2 && value.starts_with('/') && value.ends_with('/') {
+            let regex = RegexBuilder::new(&value[1..value.len() - 1])
+                .case_insensitive(true)
+                .build()?;
+            return Ok(Pattern::Regex(regex));
+        }
+        if value.contains(['*', '?']) {
+            let pattern = value
+                .split('*')
+                .map(|part| {
+                    part.split('?')
+                        .map(regex::escape)
+                        .collect::<Vec<_>>()
+                        .join(".")
+                })
+                .collect::<Vec<_>>()
+                .join(".*");
+            let regex = RegexBuilder::new(&format!("^{}$", pattern))
+                .case_insensitive(true)
+                .build()?;
+            return Ok(Pattern::Regex(regex));
+        }
+        Ok(Pattern::Exact(value.to_owned()))
+    }
+
+    fn parse_comparison(value: &str) -> Result<Self, ParseError> {
+        let (comparison, number) = [
+            ("<=", Comparison::Le),
+            (">=", Comparison::Ge),
+            ("<", Comparison::Lt),
+            (">", Comparison::Gt),
+            ("=", Comparison::Eq),
+        ]
+        .iter()
+        .find_map(|(prefix, comparison)| {
+            value
+                .strip_prefix(prefix)
+                .map(|number| (*comparison, number))
+        })
+        .unwrap_or((Comparison::Eq, value));
+        let number = number
+            .parse()
+            .map_err(|_| format!("expected a YYYYMMDD date, got {:?}", number))?;
+        Ok(Pattern::Compare(comparison, number))
+    }
+
+    fn matches(&self, value: &str) -> bool {
+        match self {
+            Pattern::Exact(expected) => value.eq_ignore_ascii_case(expected),
+            Pattern::Regex(regex) => regex.is_match(value),
+            Pattern::Compare(comparison, expected) => {
+                let Ok(value) = value.parse::<i64>() else {
+                    return false;
+                };
+                match comparison {
+                    Comparison::Lt => value < *expected,
+                    Comparison::Le => value <= *expected,
+                    Comparison::Eq => value == *expected,
+                    Comparison::Ge => value >= *expected,
+                    Comparison::Gt => value > *expected,
+                }
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Term {
+    position: Position,
+    field: Field,
+    pattern: Pattern,
+}
+
+impl Term {
+    fn parse(word: &str) -> Result<Self, ParseError> {
+        let (key, value) = match word.split_once(':') {
+            // `:shortcode:` and emoticons like `:)` are names, not fields
+            Some(("", _)) | None => ("name", word),
+            Some((key, value)) => (key, value),
+        };
+        if value.is_empty() {
+            return Err(format!("missing value for `{}:`", key).into());
+        }
+
+        let (position, field) = match key.split_once('.') {
+            Some(("base", field)) => (Position::Base, field),
+            Some(("pair", field)) => (Position::Pair, field),
+            Some(_) => return Err(format!("unknown field `{}`", key).into()),
+            None => match key {
+                "base" => (Position::Base, "names"),
+                "pair" => (Position::Pair, "names"),
+                "name" => (Position::Either, "names"),
+                key => (Position::Either, key),
+            },
+        };
+
+        let field = match field {
+            "names" if !key.contains('.') => Field::Names,
+            "short_name" => Field::ShortName,
+            "name" if position != Position::Either => Field::Name,
+            "codepoint" => Field::Codepoint,
+            "category" => Field::Category,
+            "subcategory" => Field::Subcategory,
+            "d" if position == Position::Either => Field::D,
+            "date" if position == Position::Either => Field::Date,
+            _ => return Err(format!("unknown field `{}`", key).into()),
+        };
+
+        let pattern = match field {
+            Field::Date => Pattern::parse_comparison(value)?,
+            _ => Pattern::parse(value)?,
+        };
+
+        Ok(Term {
+            position,
+            field,
+            pattern,
+        })
+    }
+
+    fn matches_emoji(&self, emoji: &Emoji) -> bool {
+        match (self.field, &self.pattern) {
+            (Field::Names, Pattern::Exact(name)) => emoji.is_named(name),
+            (Field::Names, pattern) => emoji.names().any(|name| pattern.matches(name)),
+            (Field::Codepoint, Pattern::Exact(codepoint)) => emoji.has_codepoint(codepoint),
+            (Field::ShortName, _) => self.pattern.matches(&emoji.short_name),
+            (Field::Name, _) => self.pattern.matches(&emoji.name),
+            (Field::Codepoint, _) => self.pattern.matches(&emoji.codepoint),
+            (Field::Category, _) => self.pattern.matches(&emoji.category),
+            (Field::Subcategory, _) => self.pattern.matches(&emoji.subcategory),
+            (Field::D, _) | (Field::Date, _) => false,
+        }
+    }
+
+    fn matches_pair(&self, pair: &EmojiPair) -> bool {
+        match (self.field, self.position) {
+            (Field::D, _) => self.pattern.matches(&pair.d),
+            (Field::Date, _) => self.pattern.matches(&pair.date().to_string()),
+            (_, Position::Base) => self.matches_emoji(&pair.base),
+            (_, Position::Pair) => self.matches_emoji(&pair.pair),
+            (_, Position::Either) => {
+                self.matches_emoji(&pair.base) || self.matches_emoji(&pair.pair)
+            }
+        }
+    }
+}
+
+/// A filter over emoji pairs, e.g. `base:cat AND category:"Food & Drink"`.
+///
+/// A term is `field:value`, or just `value` to match either emoji by name
+/// (which includes `:shortcode:`s and emoticons like `:)`).
+/// Fields are `name`, `base`, `pair` (short names and their aliases, text
+/// shortcuts, emoji characters or codepoints), `codepoint`, `category`,
+/// `subcategory`, `d`, `date`, and `base.<field>`/`pair.<field>` to only check
+/// one side (`<field>` being `short_name`, `name`, `codepoint`, `category` or
+/// `subcategory`). Values are case-insensitive and may be globs (`cat*`),
+/// regexes (`/^cat_?face$/`) or, for `date`, comparisons (`>=20220101`).
+/// Terms combine with `AND` (or just whitespace), `OR`, `NOT` and parentheses.
+#[derive(Clone, Debug)]
+pub enum Query {
+    Term(Term),
+    Not(Box<Query>),
+    And(Box<Query>, Box<Query>),
+    Or(Box<Query>, Box<Query>),
+}
+
+impl Query {
+    /// Same as the `--name` option: either emoji is `name`, see `Emoji::is_named`.
+    pub fn name(name: &str) -> Self {
+        Query::Term(Term {
+            position: Position::Either,
+            field: Field::Names,
+            pattern: Pattern::Exact(name.to_owned()),
+        })
+    }
+
+    pub fn and(self, other: Query) -> Self {
+        Query::And(Box::new(self), Box::new(other))
+    }
+
+    /// Combines optional `--name` and `--query` options into one filter.
+    pub fn from_options(name: &Option<String>, query: &Option<Query>) -> Option<Query> {
+        match (name.as_deref().map(Query::name), query.clone()) {
+            (Some(name), Some(query)) => Some(name.and(query)),
+            (name, query) => name.or(query),
+        }
+    }
+
+    pub fn matches(&self, pair: &EmojiPair) -> bool {
+        match self {
+            Query::Term(term) => term.matches_pair(pair),
+            Query::Not(query) => !query.matches(pair),
+            Query::And(a, b) => a.matches(pair) && b.matches(pair),
+            Query::Or(a, b) => a.matches(pair) || b.matches(pair),
+        }
+    }
+
+    /// Checks a single emoji, as if it were both the base and the pair.
+    /// `d` and `date` terms never match.
+    pub fn matches_emoji(&self, emoji: &Emoji) -> bool {
+        match self {
+            Query::Term(term) => term.matches_emoji(emoji),
+            Query::Not(query) => !query.matches_emoji(emoji),
+            Query::And(a, b) => a.matches_emoji(emoji) && b.matches_emoji(emoji),
+            Query::Or(a, b) => a.matches_emoji(emoji) || b.matches_emoji(emoji),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Token {
+    Open,
+    Close,
+    And,
+    Or,
+    Not,
+    Word(String),
+}
+
+fn tokenize(input: &str) -> Result<Vec<Token>, ParseError> {
+    let mut tokens = Vec::new();
+    let mut chars = input.chars().peekable();
+
+    while let Some(&c) = chars.peek() {
+        match c {
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            '(' => {
+                chars.next();
+                tokens.push(Token::Open);
+            }
+            ')' => {
+                chars.next();
+                tokens.push(Token::Close);
+            }
+            _ => {
+                let mut word = String::new();
+                let mut quoted = false;
+                while let Some(&c) = chars.peek() {
+                    // the parentheses of emoticons like `:)` and `:-(` are
+                    // part of them, unlike those around `(:cat:)`
+                    let emoticon =
+                        word.starts_with(':') && !word.chars().any(|c| c.is_alphanumeric());
+                    if c.is_whitespace() || (c == '(' || c == ')') && !emoticon {
+                        break;
+                    }
+                    chars.next();
+                    match c {
+                        '"' => {
+                            quoted = true;
+                            loop {
+                                match chars.next() {
+                                    Some('"') => break,
+                                    Some(c) => word.push(c),
+                                    None => return Err("unclosed `\"` in query".into()),
+                                }
+                            }
+                        }
+                        // regexes may contain whitespace and parentheses
+                        '/' if word.is_empty() || word.ends_with(':') => {
+                            word.push(c);
+                            loop {
+                                match chars.next() {
+                                    Some('/') => break,
+                                    Some('\\') => {
+                                        word.push('\\');
+                                        word.extend(chars.next());
+                                    }
+                                    Some(c) => word.push(c),
+                                    None => return Err("unclosed `/` in query".into()),
+                                }
+                            }
+                            word.push('/');
+                        }
+                        c => word.push(c),
+                    }
+                }
+                tokens.push(match word.as_str() {
+                    "AND" if !quoted => Token::And,
+                    "OR" if !quoted => Token::Or,
+                    "NOT" if !quoted => Token::Not,
+                    _ => Token::Word(word),
+                });
+            }
+        }
+    }
+
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    index: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.index)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.index).cloned();
+        self.index += 1;
+        token
+    }
+
+    fn or(&mut self) -> Result<Query, ParseError> {
+        let mut query = self.and()?;
+        while self.peek() == Some(&Token::Or) {
+            self.next();
+            query = Query::Or(Box::new(query), Box::new(self.and()?));
+        }
+        Ok(query)
+    }
+
+    fn and(&mut self) -> Result<Query, ParseError> {
+        let mut query = self.not()?;
+        loop {
+            match self.peek() {
+                Some(Token::And) => {
+                    self.next();
+                }
+                Some(Token::Open) | Some(Token::Not) | Some(Token::Word(_)) => {}
+                _ => return Ok(query),
+            }
+            query = Query::And(Box::new(query), Box::new(self.not()?));
+        }
+    }
+
+    fn not(&mut self) -> Result<Query, ParseError> {
+        if self.peek() == Some(&Token::Not) {
+            self.next();
+            return Ok(Query::Not(Box::new(self.not()?)));
+        }
+        self.atom()
+    }
+
+    fn atom(&mut self) -> Result<Query, ParseError> {
+        match self.next() {
+            Some(Token::Open) => {
+                let query = self.or()?;
+                match self.next() {
+                    Some(Token::Close) => Ok(query),
+                    _ => Err("missing `)` in query".into()),
+                }
+            }
+            Some(Token::Word(word)) => Ok(Query::Term(Term::parse(&word)?)),
+            Some(token) => Err(format!("unexpected {:?} in query", token).into()),
+            None => Err("unexpected end of query".into()),
+        }
+    }
+}
+
+impl FromStr for Query {
+    type Err = ParseError;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let mut parser = Parser {
+            tokens: tokenize(input)?,
+            index: 0,
+        };
+        let query = parser.or()?;
+        match parser.next() {
+            None => Ok(query),
+            Some(token) => Err(format!("unexpected {:?} in query", token).into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A fixture with a `<short_name>2` alias.
+    fn emoji(codepoint: &str, short_name: &str, category: &str) -> Emoji {
+        let mut emoji = Emoji::test_fixture(codepoint, short_name, category);
+        emoji.short_names = vec![short_name.to_owned(), format!("{}2", short_name)];
+        emoji
+    }
+
+    fn pairs() -> Vec<EmojiPair> {
+        let cat = emoji("1F431", "cat", "Animals & Nature");
+        let pizza = emoji("1F355", "pizza", "Food & Drink");
+        let mut smiley = emoji("1F603", "smiley", "Smileys & Emotion");
+        smiley.texts = vec![":)".to_owned()];
+        vec![
+            EmojiPair::new("3e9", &cat, &pizza),
+            EmojiPair::new("5209", &pizza, &smiley),
+            EmojiPair::new("65", &smiley, &cat),
+        ]
+    }
+
+    /// The names of the pairs `query` matches.
+    fn matching(query: &str) -> Vec<String> {
+        let query: Query = query.parse().unwrap();
+        pairs()
+            .into_iter()
+            .filter(|pair| query.matches(pair))
+            .map(|pair| pair.name)
+            .collect()
+    }
+
+    #[test]
+    fn bare_words_match_either_emoji_by_name() {
+        assert_eq!(matching("cat"), ["cat_pizza", "smiley_cat"]);
+        assert_eq!(matching("CAT2"), ["cat_pizza", "smiley_cat"]);
+        assert_eq!(matching("🐱"), ["cat_pizza", "smiley_cat"]);
+        assert_eq!(matching("1f431"), ["cat_pizza", "smiley_cat"]);
+        assert_eq!(matching("name:pizza"), ["cat_pizza", "pizza_smiley"]);
+        assert_eq!(matching(":cat:"), ["cat_pizza", "smiley_cat"]);
+        assert_eq!(matching(":)"), ["pizza_smiley", "smiley_cat"]);
+        assert_eq!(matching("(:smiley:)"), ["pizza_smiley", "smiley_cat"]);
+        assert_eq!(
+            matching("base:\":)\" OR (:pizza: :) )"),
+            ["pizza_smiley", "smiley_cat"]
+        );
+    }
+
+    #[test]
+    fn positions() {
+        assert_eq!(matching("base:cat"), ["cat_pizza"]);
+        assert_eq!(matching("pair:cat"), ["smiley_cat"]);
+        assert_eq!(matching("pair.short_name:cat"), ["smiley_cat"]);
+        assert_eq!(matching("base.name:\"pizza\""), ["pizza_smiley"]);
+        assert_eq!(matching("base.category:food*"), ["pizza_smiley"]);
+        assert_eq!(matching("pair.codepoint:1f355"), ["cat_pizza"]);
+    }
+
+    #[test]
+    fn globs_and_regexes() {
+        assert_eq!(matching("base:c?t"), ["cat_pizza"]);
+        assert_eq!(matching("base:*z*"), ["pizza_smiley"]);
+        assert_eq!(
+            matching("base:/^(cat|smiley)$/"),
+            ["cat_pizza", "smiley_cat"]
+        );
+        assert_eq!(
+            matching("category:/smileys (&|and) emotion/"),
+            ["pizza_smiley", "smiley_cat"]
+        );
+    }
+
+    #[test]
+    fn dates() {
+        assert_eq!(matching("d:3e9"), ["cat_pizza"]);
+        assert_eq!(matching("date:20201001"), ["cat_pizza"]);
+        assert_eq!(matching("date:=20201001"), ["cat_pizza"]);
+        assert_eq!(matching("date:>20201001"), ["pizza_smiley"]);
+        assert_eq!(matching("date:>=20221001"), ["pizza_smiley"]);
+        assert_eq!(matching("date:<20201001"), ["smiley_cat"]);
+        assert_eq!(matching("date:<=20201001"), ["cat_pizza", "smiley_cat"]);
+    }
+
+    #[test]
+    fn operators() {
+        assert_eq!(matching("cat pizza"), ["cat_pizza"]);
+        assert_eq!(matching("cat AND pizza"), ["cat_pizza"]);
+        assert_eq!(
+            matching("base:cat OR base:pizza"),
+            ["cat_pizza", "pizza_smiley"]
+        );
+        assert_eq!(matching("NOT cat"), ["pizza_smiley"]);
+        assert_eq!(matching("NOT NOT cat"), ["cat_pizza", "smiley_cat"]);
+        // AND binds tighter than OR
+        assert_eq!(
+            matching("base:smiley OR base:cat AND pair:cat"),
+            ["smiley_cat"]
+        );
+        assert_eq!(
+            matching("(base:smiley OR base:cat) AND NOT pair:pizza"),
+            ["smiley_cat"]
+        );
+        assert_eq!(matching("\"AND\""), Vec::<String>::new());
+    }
+
+    #[test]
+    fn from_options_combines_name_and_query() {
+        let query = Some("base:cat".parse().unwrap());
+        let combined = Query::from_options(&Some("pizza".to_owned()), &query).unwrap();
+        let names: Vec<_> = pairs()
+            .into_iter()
+            .filter(|pair| combined.matches(pair))
+            .map(|pair| pair.name)
+            .collect();
+        assert_eq!(names, ["cat_pizza"]);
+        assert!(Query::from_options(&None, &None).is_none());
+    }
+
+    #[test]
+    fn rejects_invalid_queries() {
+        for query in [
+            "",
+            "AND",
+            "cat AND",
+            "NOT",
+            "(cat",
+            "cat)",
+            "()",
+            "\"cat",
+            "base:/cat",
+            "base:/[/",
+            "base:",
+            "nope:cat",
+            "base.d:3e9",
+            "base.names:cat",
+            "side.name:cat",
+            "name:",
+            "date:>=soon",
+            "date:2020*",
+        ] {
+            assert!(
+                query.parse::<Query>().is_err(),
+                "{:?} should be rejected",
+                query
+            );
+        }
+    }
+}
@@ -0,0 +1,174 @@
+use image::{imageops, DynamicImage, Rgba, RgbaImage};
+
+// 5x7 glyphs (plus a row for descenders) for the characters in short names,
+// one row per byte with the leftmost pixel in the 5th bit
+const GLYPH_WIDTH: u32 = 5;
+const GLYPH_HEIGHT: u32 = 8;
+
+#[rustfmt::skip]
+fn glyph(c: char) -> [u8; 8] {
+    match c.to_ascii_lowercase() {
+        'a' => [0b00000, 0b00000, 0b01110, 0b00001, 0b01111, 0b10001, 0b01111, 0b00000],
+        'b' => [0b10000, 0b10000, 0b10110, 0b11001, 0b10001, 0b10001, 0b11110, 0b00000],
+        'c' => [0b00000, 0b00000, 0b01110, 0b10000, 0b10000, 0b10001, 0b01110, 0b00000],
+        'd' => [0b00001, 0b00001, 0b01101, 0b10011, 0b10001, 0b10001, 0b01111, 0b00000],
+        'e' => [0b00000, 0b00000, 0b01110, 0b10001, 0b11111, 0b10000, 0b01110, 0b00000],
+        'f' => [0b00110, 0b01001, 0b01000, 0b11100, 0b01000, 0b01000, 0b01000, 0b00000],
+        'g' => [0b00000, 0b00000, 0b01111, 0b10001, 0b10001, 0b01111, 0b00001, 0b01110],
+        'h' => [0b10000, 0b10000, 0b10110, 0b11001, 0b10001, 0b10001, 0b10001, 0b00000],
+        'i' => [0b00100, 0b00000, 0b01100, 0b00100, 0b00100, 0b00100, 0b01110, 0b00000],
+        'j' => [0b00010, 0b00000, 0b00110, 0b00010, 0b00010, 0b00010, 0b10010, 0b01100],
+        'k' => [0b10000, 0b10000, 0b10010, 0b10100, 0b11000, 0b10100, 0b10010, 0b00000],
+        'l' => [0b01100, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0b01110, 0b00000],
+        'm' => [0b00000, 0b00000, 0b11010, 0b10101, 0b10101, 0b10001, 0b10001, 0b00000],
+        'n' => [0b00000, 0b00000, 0b10110, 0b11001, 0b10001, 0b10001, 0b10001, 0b00000],
+        'o' => [0b00000, 0b00000, 0b01110, 0b10001, 0b10001, 0b10001, 0b01110, 0b00000],
+        'p' => [0b00000, 0b00000, 0b11110, 0b10001, 0b10001, 0b11110, 0b10000, 0b10000],
+        'q' => [0b00000, 0b00000, 0b01111, 0b10001, 0b10001, 0b01111, 0b00001, 0b00001],
+        'r' => [0b00000, 0b00000, 0b10110, 0b11001, 0b10000, 0b10000, 0b10000, 0b00000],
+        's' => [0b00000, 0b00000, 0b01110, 0b10000, 0b01110, 0b00001, 0b11110, 0b00000],
+        't' => [0b01000, 0b01000, 0b11100, 0b01000, 0b01000, 0b01001, 0b00110, 0b00000],
+        'u' => [0b00000, 0b00000, 0b10001, 0b10001, 0b10001, 0b10011, 0b01101, 0b00000],
+        'v' => [0b00000, 0b00000, 0b10001, 0b10001, 0b10001, 0b01010, 0b00100, 0b00000],
+        'w' => [0b00000, 0b00000, 0b10001, 0b10001, 0b10101, 0b10101, 0b01010, 0b00000],
+        'x' => [0b00000, 0b00000, 0b10001, 0b01010, 0b00100, 0b01010, 0b10001, 0b00000],
+        'y' => [0b00000, 0b00000, 0b10001, 0b10001, 0b10001, 0b01111, 0b00001, 0b01110],
+        'z' => [0b00000, 0b00000, 0b11111, 0b00010, 0b00100, 0b01000, 0b11111, 0b00000],
+        '0' => [0b01110, 0b10001, 0b10011, 0b10101, 0b11001, 0b10001, 0b01110, 0b00000],
+        '1' => [0b00100, 0b01100, 0b00100, 0b00100, 0b00100, 0b00100, 0b01110, 0b00000],
+        '2' => [0b01110, 0b10001, 0b00001, 0b00010, 0b00100, 0b01000, 0b11111, 0b00000],
+        '3' => [0b11111, 0b00010, 0b00100, 0b00010, 0b00001, 0b10001, 0b01110, 0b00000],
+        '4' => [0b00010, 0b00110, 0b01010, 0b10010, 0b11111, 0b00010, 0b00010, 0b00000],
+        '5' => [0b11111, 0b10000, 0b11110, 0b00001, 0b00001, 0b10001, 0b01110, 0b00000],
+        '6' => [0b00110, 0b01000, 0b10000, 0b11110, 0b10001, 0b10001, 0b01110, 0b00000],
+        '7' => [0b11111, 0b00001, 0b00010, 0b00100, 0b01000, 0b01000, 0b01000, 0b00000],
+        '8' => [0b01110, 0b10001, 0b10001, 0b01110, 0b10001, 0b10001, 0b01110, 0b00000],
+        '9' => [0b01110, 0b10001, 0b10001, 0b01111, 0b00001, 0b00010, 0b01100, 0b00000],
+        '_' => [0b00000, 0b00000, 0b00000, 0b00000, 0b00000, 0b00000, 0b11111, 0b00000],
+        '-' => [0b00000, 0b00000, 0b00000, 0b11111, 0b00000, 0b00000, 0b00000, 0b00000],
+        '+' => [0b00000, 0b00100, 0b00100, 0b11111, 0b00100, 0b00100, 0b00000, 0b00000],
+        '.' => [0b00000, 0b00000, 0b00000, 0b00000, 0b00000, 0b01100, 0b01100, 0b00000],
+        ' ' => [0; 8],
+        _ => [0b01110, 0b10001, 0b00001, 0b00010, 0b00100, 0b00000, 0b00100, 0b00000],
+    }
+}
+
+/// Draws `text` with its top left corner at `x`, `y`, each glyph pixel
+/// `scale` pixels wide. Text that doesn't fit in `max_width` is cut off.
+fn draw_text(image: &mut RgbaImage, text: &str, x: u32, y: u32, scale: u32, max_width: u32) {
+    let color = Rgba([48, 48, 48, 255]);
+    let advance = (GLYPH_WIDTH + 1) * scale;
+    let fits = (max_width / advance) as usize;
+    for (i, c) in text.chars().take(fits).enumerate() {
+        let left = x + i as u32 * advance;
+        for (row, bits) in glyph(c).iter().enumerate() {
+            for col in 0..GLYPH_WIDTH {
+                if bits & (1 << (GLYPH_WIDTH - 1 - col)) == 0 {
+                    continue;
+                }
+                for dy in 0..scale {
+                    for dx in 0..scale {
+                        let (px, py) = (left + col * scale + dx, y + row as u32 * scale + dy);
+                        if px < image.width() && py < image.height() {
+                            image.put_pixel(px, py, color);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// How `contact_sheet` lays out its tiles.
+#[derive(Clone, Copy, Debug)]
+pub struct SheetOptions {
+    pub columns: u32,
+    /// width and height of each image, in pixels
+    pub tile_size: u32,
+    /// draw each tile's label under it
+    pub labels: bool,
+    /// transparent when `None`
+    pub background: Option<Rgba<u8>>,
+}
+
+impl SheetOptions {
+    /// The padding around each tile, in pixels.
+    pub fn padding(&self) -> u32 {
+        self.tile_size / 8
+    }
+
+    fn label_scale(&self) -> u32 {
+        (self.tile_size / 64).max(1)
+    }
+
+    /// The width and height each tile takes up, including padding and label.
+    pub fn slot_size(&self) -> (u32, u32) {
+        let width = self.tile_size + self.padding() * 2;
+        let label = if self.labels {
+            (GLYPH_HEIGHT + 2) * self.label_scale()
+        } else {
+            0
+        };
+        (width, width + label)
+    }
+}
+
+/// Tiles `tiles` into rows of `options.columns`, each image scaled to
+/// `options.tile_size`. `None` leaves its tile empty, but keeps the label.
+pub fn contact_sheet(tiles: &[(Option<&DynamicImage>, &str)], options: SheetOptions) -> RgbaImage {
+    let mut sheet = blank_sheet(tiles.len(), options);
+    for (i, (image, label)) in tiles.iter().enumerate() {
+        draw_tile(&mut sheet, i, *image, label, options);
+    }
+    sheet
+}
+
+/// An empty sheet with room for `count` tiles, for `draw_tile` to fill in one
+/// at a time without keeping every image in memory.
+pub fn blank_sheet(count: usize, options: SheetOptions) -> RgbaImage {
+    let columns = options.columns.max(1);
+    let rows = (count as u32).div_ceil(columns);
+    let (slot_width, slot_height) = options.slot_size();
+    let background = options.background.unwrap_or(Rgba([0, 0, 0, 0]));
+    RgbaImage::from_pixel(
+        slot_width * columns.min(count as u32).max(1),
+        slot_height * rows.max(1),
+        background,
+    )
+}
+
+/// Draws tile `i` of a sheet from `blank_sheet`.
+pub fn draw_tile(
+    sheet: &mut RgbaImage,
+    i: usize,
+    image: Option<&DynamicImage>,
+    label: &str,
+    options: SheetOptions,
+) {
+    let columns = options.columns.max(1);
+    let (slot_width, slot_height) = options.slot_size();
+    let x = (i as u32 % columns) * slot_width + options.padding();
+    let y = (i as u32 / columns) * slot_height + options.padding();
+    if let Some(image) = image {
+        let tile = image.resize(
+            options.tile_size,
+            options.tile_size,
+            imageops::FilterType::Triangle,
+        );
+        // centered, for images that aren't square
+        let left = x + (options.tile_size - tile.width()) / 2;
+        let top = y + (options.tile_size - tile.height()) / 2;
+        imageops::overlay(sheet, &tile.to_rgba8(), left as i64, top as i64);
+    }
+    if options.labels {
+        let scale = options.label_scale();
+        draw_text(
+            sheet,
+            label,
+            x,
+            y + options.tile_size + scale,
+            scale,
+            options.tile_size + options.padding(),
+        );
+    }
+}
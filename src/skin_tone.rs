@@ -0,0 +1,103 @@
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+/// The five Fitzpatrick skin tone modifiers.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum SkinTone {
+    Light,
+    MediumLight,
+    Medium,
+    MediumDark,
+    Dark,
+}
+
+impl SkinTone {
+    pub const ALL: [SkinTone; 5] = [
+        SkinTone::Light,
+        SkinTone::MediumLight,
+        SkinTone::Medium,
+        SkinTone::MediumDark,
+        SkinTone::Dark,
+    ];
+
+    /// The modifier's codepoint, e.g. `1f3fb`.
+    pub fn modifier(&self) -> &'static str {
+        match self {
+            SkinTone::Light => "1f3fb",
+            SkinTone::MediumLight => "1f3fc",
+            SkinTone::Medium => "1f3fd",
+            SkinTone::MediumDark => "1f3fe",
+            SkinTone::Dark => "1f3ff",
+        }
+    }
+
+    /// e.g. `medium-light`
+    pub fn label(&self) -> &'static str {
+        match self {
+            SkinTone::Light => "light",
+            SkinTone::MediumLight => "medium-light",
+            SkinTone::Medium => "medium",
+            SkinTone::MediumDark => "medium-dark",
+            SkinTone::Dark => "dark",
+        }
+    }
+
+    /// Slack's numbering, `skin-tone-2` through `skin-tone-6`.
+    pub fn number(&self) -> u8 {
+        match self {
+            SkinTone::Light => 2,
+            SkinTone::MediumLight => 3,
+            SkinTone::Medium => 4,
+            SkinTone::MediumDark => 5,
+            SkinTone::Dark => 6,
+        }
+    }
+
+    /// The tone of a `skin_variations` key in `emoji.json`, e.g. `1F3FB`, or
+    /// `1F3FB-1F3FB` for emoji with two people. Keys mixing tones have none.
+    pub fn from_variation_key(key: &str) -> Option<SkinTone> {
+        let key = key.to_lowercase();
+        let mut modifiers = key.split('-');
+        let first = modifiers.next()?;
+        if modifiers.any(|modifier| modifier != first) {
+            return None;
+        }
+        SkinTone::ALL
+            .into_iter()
+            .find(|tone| tone.modifier() == first)
+    }
+}
+
+impl fmt::Display for SkinTone {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.label())
+    }
+}
+
+impl FromStr for SkinTone {
+    type Err = Box<dyn Error + Send + Sync>;
+
+    /// Accepts a label (`medium-light`), Slack's number (`3`, `skin-tone-3`),
+    /// the modifier's codepoint (`1f3fc`) or the modifier itself (`🏼`).
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let input = input.trim().to_lowercase().replace('_', "-");
+        let number = input.strip_prefix("skin-tone-").unwrap_or(&input);
+        let modifier = crate::emoji::codepoint_from_chars(&input);
+        SkinTone::ALL
+            .into_iter()
+            .find(|tone| {
+                tone.label() == input
+                    || tone.number().to_string() == number
+                    || tone.modifier() == input
+                    || tone.modifier() == modifier
+            })
+            .ok_or_else(|| {
+                format!(
+                    "unknown skin tone {:?}, expected one of light, medium-light, medium, medium-dark, dark (or 2-6)",
+                    input
+                )
+                .into()
+            })
+    }
+}
@@ -0,0 +1,49 @@
+use std::error::Error;
+
+use emoji_kitchen::emoji::{self, EmojiDB};
+use emoji_kitchen::skin_tone::SkinTone;
+
+/// Prints each skin tone variant of `input` with the number of pairs in
+/// `pairs.txt` that use it.
+pub fn skin_tones(db: &EmojiDB, input: &str) -> Result<(), Box<dyn Error>> {
+    let emoji = emoji::find_emoji(&db.emoji_map, input)
+        .ok_or_else(|| Box::<dyn Error>::from(format!("no emoji named {:?}", input)))?;
+    // a variant was asked for, list its siblings
+    let emoji = match &emoji.skin_tone_of {
+        Some(codepoint) => &db.emoji_map[codepoint],
+        None => emoji,
+    };
+
+    println!(
+        "{} {}: {} pairs",
+        emoji.short_name,
+        emoji.codepoint,
+        db.pairs_with(emoji).count()
+    );
+
+    let variants: Vec<_> = SkinTone::ALL
+        .into_iter()
+        .filter_map(|tone| Some((tone, emoji.skin_tone_variant(&db.emoji_map, tone)?)))
+        .collect();
+
+    if variants.is_empty() {
+        println!("  no skin tone variants");
+        return Ok(());
+    }
+
+    for (tone, variant) in variants {
+        let artwork = match db.pairs_with(variant).count() {
+            0 => "no artwork".to_owned(),
+            count => format!("{} pairs", count),
+        };
+        println!(
+            "  {:<12} {:<18} {:<28} {}",
+            tone.label(),
+            variant.codepoint,
+            variant.short_name,
+            artwork
+        );
+    }
+
+    Ok(())
+}
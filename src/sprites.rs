@@ -0,0 +1,103 @@
+use std::error::Error;
+use std::fs;
+use std::path::PathBuf;
+
+use emoji_kitchen::atlas::Atlas;
+use emoji_kitchen::emoji::EmojiPair;
+use emoji_kitchen::manifest::Manifest;
+use image::RgbaImage;
+use indicatif::{ProgressBar, ProgressStyle};
+
+pub struct Options {
+    /// the download directory the images are read from
+    pub input: PathBuf,
+    pub output: PathBuf,
+    /// width and height of each sprite, in pixels
+    pub cell_size: u32,
+    /// largest width and height of a sheet, in pixels
+    pub max_size: u32,
+    /// filename of the atlas and sheets, without extension
+    pub name: String,
+    /// class name prefix for the CSS, none when `None`
+    pub css_prefix: Option<String>,
+}
+
+fn save_sheet(
+    atlas: &mut Atlas,
+    options: &Options,
+    cells: &[(String, RgbaImage)],
+) -> Result<(), Box<dyn Error>> {
+    let filename = format!("{}-{}.png", options.name, atlas.sheets.len());
+    let sheet = atlas.add_sheet(&filename, cells);
+    sheet.save(options.output.join(&filename))?;
+    Ok(())
+}
+
+/// Packs the downloaded images of `pairs` into sprite sheets, and writes a
+/// JSON atlas (and optionally CSS) keyed by pair name next to them.
+pub fn sprites(pairs: &[EmojiPair], options: Options) -> Result<(), Box<dyn Error>> {
+    fs::create_dir_all(&options.output)?;
+    // a missing or unreadable manifest just means default filenames
+    let files = Manifest::load(&options.input)
+        .unwrap_or_default()
+        .filenames_by_url();
+
+    let mut atlas = Atlas::new(options.cell_size, options.max_size);
+    let mut cells = Vec::new();
+    let mut missing = 0;
+
+    let progress = ProgressBar::new(pairs.len() as u64);
+    progress.set_style(
+        ProgressStyle::with_template("[{elapsed_precise}] {wide_bar} {pos}/{len} ETA {eta}")
+            .unwrap(),
+    );
+
+    for pair in pairs {
+        progress.inc(1);
+        let filename = files.get(&pair.image_url).unwrap_or(&pair.filename);
+        let image = match image::open(options.input.join(filename)) {
+            Err(_) => {
+                missing += 1;
+                continue;
+            }
+            Ok(image) => image,
+        };
+        cells.push((pair.name.to_owned(), atlas.cell(&image)));
+
+        if cells.len() == atlas.cells_per_sheet() {
+            save_sheet(&mut atlas, &options, &cells)?;
+            cells.clear();
+        }
+    }
+    if !cells.is_empty() {
+        save_sheet(&mut atlas, &options, &cells)?;
+    }
+
+    progress.finish();
+
+    if missing > 0 {
+        println!(
+            "⚠️ {} pairs have no image in {} (run `download` first)",
+            missing,
+            options.input.display()
+        );
+    }
+
+    let atlas_path = options.output.join(format!("{}.json", options.name));
+    fs::write(&atlas_path, serde_json::to_string_pretty(&atlas)?)?;
+    println!("✅ {}", atlas_path.display());
+
+    if let Some(prefix) = &options.css_prefix {
+        let css_path = options.output.join(format!("{}.css", options.name));
+        fs::write(&css_path, atlas.css(prefix))?;
+        println!("✅ {}", css_path.display());
+    }
+
+    println!(
+        "ℹ️ {} sprites in {} sheets",
+        atlas.sprites.len(),
+        atlas.sheets.len()
+    );
+
+    Ok(())
+}
@@ -0,0 +1,285 @@
+use std::error::Error;
+use std::str::FromStr;
+
+use crate::emoji::{Emoji, EmojiPair};
+
+/// The same name `EmojiPair::filename` uses, without the extension.
+pub const DEFAULT_TEMPLATE: &str =
+    "{sort_order}.{d}.{base.codepoint}.{pair.codepoint}.{base.short_name}.{pair.short_name}";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Side {
+    Base,
+    Pair,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum EmojiField {
+    Codepoint,
+    Name,
+    ShortName,
+    Category,
+    Subcategory,
+    SortOrder,
+}
+
+impl EmojiField {
+    fn parse(field: &str) -> Option<Self> {
+        Some(match field {
+            "codepoint" => EmojiField::Codepoint,
+            "name" => EmojiField::Name,
+            "short_name" => EmojiField::ShortName,
+            "category" => EmojiField::Category,
+            "subcategory" => EmojiField::Subcategory,
+            "sort_order" => EmojiField::SortOrder,
+            _ => return None,
+        })
+    }
+
+    fn render(&self, emoji: &Emoji) -> String {
+        match self {
+            EmojiField::Codepoint => emoji.codepoint.to_owned(),
+            EmojiField::Name => emoji.name.to_lowercase(),
+            EmojiField::ShortName => emoji.short_name.to_owned(),
+            EmojiField::Category => emoji.category.to_owned(),
+            EmojiField::Subcategory => emoji.subcategory.to_owned(),
+            EmojiField::SortOrder => emoji.sort_order.to_string(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Part {
+    Literal(String),
+    Emoji(Side, EmojiField),
+    /// `EmojiPair.name`
+    Name,
+    /// the raw hex date code from `pairs.txt`
+    D,
+    /// the decoded `YYYYMMDD` date
+    Date,
+    SortOrder,
+    /// the base emoji's category
+    Category,
+}
+
+/// A relative output path for a pair, e.g. `{base.short_name}/{name}`.
+///
+/// Placeholders are `{name}`, `{d}`, `{date}`, `{sort_order}`, `{category}`
+/// and `{base.<field>}`/`{pair.<field>}`, where `<field>` is one of
+/// `codepoint`, `name`, `short_name`, `category`, `subcategory` or
+/// `sort_order`. `/` in the template creates subdirectories. The extension is
+/// not part of the template.
+#[derive(Clone, Debug)]
+pub struct FilenameTemplate {
+    parts: Vec<Part>,
+}
+
+impl FilenameTemplate {
+    fn parse_placeholder(placeholder: &str) -> Option<Part> {
+        Some(match placeholder {
+            "name" => Part::Name,
+            "d" => Part::D,
+            "date" => Part::Date,
+            "sort_order" => Part::SortOrder,
+            "category" => Part::Category,
+            _ => {
+                let (side, field) = placeholder.split_once('.')?;
+                let side = match side {
+                    "base" => Side::Base,
+                    "pair" => Side::Pair,
+                    _ => return None,
+                };
+                Part::Emoji(side, EmojiField::parse(field)?)
+            }
+        })
+    }
+
+    /// Prefixes the template with a directory, e.g. one folder per base emoji.
+    pub fn with_layout(mut self, layout: Layout) -> Self {
+        let dir = match layout {
+            Layout::Flat => return self,
+            Layout::Base => Part::Emoji(Side::Base, EmojiField::ShortName),
+            Layout::Category => Part::Category,
+        };
+        self.parts
+            .splice(0..0, [dir, Part::Literal("/".to_owned())]);
+        self
+    }
+
+    fn render_value(part: &Part, pair: &EmojiPair) -> String {
+        match part {
+            Part::Literal(literal) => literal.to_owned(),
+            Part::Emoji(Side::Base, field) => field.render(&pair.base),
+            Part::Emoji(Side::Pair, field) => field.render(&pair.pair),
+            Part::Name => pair.name.to_owned(),
+            Part::D => pair.d.to_owned(),
+            Part::Date => pair.date().to_string(),
+            Part::SortOrder => pair.sort_order.to_string(),
+            Part::Category => pair.base.category.to_owned(),
+        }
+    }
+
+    /// Renders the path for `pair`, without an extension. Values can't
+    /// introduce their own subdirectories; any `/` in them is replaced.
+    pub fn render(&self, pair: &EmojiPair) -> String {
+        self.parts
+            .iter()
+            .map(|part| match part {
+                Part::Literal(literal) => literal.to_owned(),
+                part => FilenameTemplate::render_value(part, pair).replace(['/', '\\'], "-"),
+            })
+            .collect()
+    }
+}
+
+impl Default for FilenameTemplate {
+    fn default() -> Self {
+        DEFAULT_TEMPLATE.parse().unwrap()
+    }
+}
+
+impl FromStr for FilenameTemplate {
+    type Err = Box<dyn Error + Send + Sync>;
+
+    fn from_str(template: &str) -> Result<Self, Self::Err> {
+        let mut parts = Vec::new();
+        let mut rest = template;
+
+        while let Some(start) = rest.find('{') {
+            if start > 0 {
+                parts.push(Part::Literal(rest[..start].to_owned()));
+            }
+            let end = rest[start..]
+                .find('}')
+                .ok_or_else(|| format!("unclosed `{{` in template {:?}", template))?;
+            let placeholder = &rest[start + 1..start + end];
+            let part = FilenameTemplate::parse_placeholder(placeholder)
+                .ok_or_else(|| format!("unknown placeholder {{{}}}", placeholder))?;
+            parts.push(part);
+            rest = &rest[start + end + 1..];
+        }
+        if !rest.is_empty() {
+            parts.push(Part::Literal(rest.to_owned()));
+        }
+
+        let literals = parts.iter().filter_map(|part| match part {
+            Part::Literal(literal) => Some(literal),
+            _ => None,
+        });
+        for literal in literals {
+            if literal.contains('}') {
+                return Err(format!("unmatched `}}` in template {:?}", template).into());
+            }
+            if literal.split('/').any(|segment| segment == "..") {
+                return Err(format!("template {:?} can't leave the output dir", template).into());
+            }
+        }
+        if template.starts_with('/') {
+            return Err(format!("template {:?} must be a relative path", template).into());
+        }
+
+        Ok(FilenameTemplate { parts })
+    }
+}
+
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+#[derive(Clone, Copy, Debug, Default)]
+pub enum Layout {
+    /// every image in the output dir
+    #[default]
+    Flat,
+    /// one folder per base emoji
+    Base,
+    /// one folder per base emoji category
+    Category,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pair() -> EmojiPair {
+        EmojiPair::new(
+            "3e9",
+            &Emoji::test_fixture("1F431", "cat", "Animals & Nature"),
+            &Emoji::test_fixture("1F355", "pizza", "Food & Drink"),
+        )
+    }
+
+    fn render(template: &str) -> String {
+        template
+            .parse::<FilenameTemplate>()
+            .unwrap()
+            .render(&pair())
+    }
+
+    #[test]
+    fn renders_placeholders() {
+        assert_eq!(render("{name}"), "cat_pizza");
+        assert_eq!(render("{base.short_name}/{pair.codepoint}"), "cat/1f355");
+        assert_eq!(render("{date}-{d}-{sort_order}"), "20201001-3e9-2");
+        assert_eq!(
+            render("{pair.name} {base.category}"),
+            "pizza Animals & Nature"
+        );
+        assert_eq!(render("no placeholders"), "no placeholders");
+    }
+
+    #[test]
+    fn default_matches_pair_filename() {
+        let pair = pair();
+        let filename = FilenameTemplate::default().render(&pair);
+        assert_eq!(format!("{}.png", filename), pair.filename);
+    }
+
+    #[test]
+    fn values_cant_add_directories() {
+        assert_eq!(render("{category}/{name}"), "Animals & Nature/cat_pizza");
+        let mut pair = pair();
+        pair.base.category = "a/b\\c".to_owned();
+        let template: FilenameTemplate = "{category}/{name}".parse().unwrap();
+        assert_eq!(template.render(&pair), "a-b-c/cat_pizza");
+    }
+
+    #[test]
+    fn layouts_prefix_a_directory() {
+        let template = || "{name}".parse::<FilenameTemplate>().unwrap();
+        let pair = pair();
+        assert_eq!(
+            template().with_layout(Layout::Flat).render(&pair),
+            "cat_pizza"
+        );
+        assert_eq!(
+            template().with_layout(Layout::Base).render(&pair),
+            "cat/cat_pizza"
+        );
+        assert_eq!(
+            template().with_layout(Layout::Category).render(&pair),
+            "Animals & Nature/cat_pizza"
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_templates() {
+        for template in [
+            "{nope}",
+            "{base.nope}",
+            "{side.name}",
+            "{}",
+            "{name",
+            "name}",
+            "{name}}",
+            "../{name}",
+            "a/../{name}",
+            "{name}/..",
+            "/{name}",
+        ] {
+            assert!(
+                template.parse::<FilenameTemplate>().is_err(),
+                "{:?} should be rejected",
+                template
+            );
+        }
+    }
+}
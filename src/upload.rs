@@ -1,16 +1,22 @@
+use std::collections::{HashMap, HashSet};
 use std::error::Error;
 use std::fs;
 use std::path::Path;
 
+use emoji_kitchen::emoji;
+use emoji_kitchen::query::Query;
+use emoji_kitchen::utils;
 use reqwest::multipart;
 use serde::Deserialize;
 
-use crate::emoji;
-use crate::utils;
+use crate::config::SlackConfig;
 
-fn get_emoji_data_from_dir(input_path: &Path, name_query: &Option<String>) -> Vec<UploadEmoji> {
+fn get_emoji_data_from_dir(
+    input_path: &Path,
+    query: &Option<Query>,
+    emoji_map: &HashMap<String, emoji::Emoji>,
+) -> Vec<UploadEmoji> {
     let paths = fs::read_dir(input_path).unwrap();
-    let emoji_map = emoji::get_emoji_map();
     let mut output = Vec::new();
 
     for dir_result in paths {
@@ -27,10 +33,10 @@ fn get_emoji_data_from_dir(input_path: &Path, name_query: &Option<String>) -> Ve
 
         let emoji = emoji_map
             .get(&codepoint)
-            .expect(&format!("⚠️ emoji data for {} not found!", &codepoint));
+            .unwrap_or_else(|| panic!("⚠️ emoji data for {} not found!", &codepoint));
 
-        match name_query {
-            Some(n) if !n.eq(&emoji.short_name) => continue,
+        match query {
+            Some(q) if !q.matches_emoji(emoji) => continue,
             Some(_) => {}
             None => {}
         }
@@ -44,21 +50,60 @@ fn get_emoji_data_from_dir(input_path: &Path, name_query: &Option<String>) -> Ve
     output
 }
 
-pub async fn upload(input_path: &Path, name_query: &Option<String>) {
-    let config = UploadConfig {
-        cookie: dotenv!("COOKIE").to_owned(),
-        token: dotenv!("TOKEN").to_owned(),
-        workspace_name: dotenv!("WORKSPACE_NAME").to_owned(),
+/// Images named `<pair name>.<ext>`, uploaded under that name.
+fn get_pair_data_from_dir(input_path: &Path, pairs: &[emoji::EmojiPair]) -> Vec<UploadEmoji> {
+    let names: HashSet<&str> = pairs.iter().map(|pair| pair.name.as_str()).collect();
+    let paths = fs::read_dir(input_path).unwrap();
+    let mut output = Vec::new();
+
+    for dir_result in paths {
+        let path = dir_result.unwrap().path();
+        let name = match path.file_stem().and_then(|stem| stem.to_str()) {
+            Some(name) if names.contains(name) => name.to_owned(),
+            _ => continue,
+        };
+
+        output.push(UploadEmoji {
+            name,
+            path: path.to_str().unwrap().to_string(),
+        });
+    }
+
+    output
+}
+
+pub async fn upload(
+    config: &SlackConfig,
+    input_path: &Path,
+    query: &Option<Query>,
+    sources: &emoji::Sources,
+) {
+    let emoji_map = match emoji::get_emoji_map(sources) {
+        Err(err) => {
+            println!("🚫 Failed to load emoji data: {}", err);
+            return;
+        }
+        Ok(map) => map,
     };
 
-    let emoji_list = get_emoji_data_from_dir(input_path, name_query);
+    let emoji_list = get_emoji_data_from_dir(input_path, query, &emoji_map);
+    upload_all(config, emoji_list).await;
+}
+
+/// Uploads the images in `input_path` named after one of `pairs`, like the
+/// ones `browse` queues.
+pub async fn upload_pairs(config: &SlackConfig, input_path: &Path, pairs: &[emoji::EmojiPair]) {
+    let emoji_list = get_pair_data_from_dir(input_path, pairs);
+    upload_all(config, emoji_list).await;
+}
 
+async fn upload_all(config: &SlackConfig, emoji_list: Vec<UploadEmoji>) {
     println!("ℹ️ {} emoji found", emoji_list.len());
 
     let mut errors: Vec<(String, Box<dyn Error>)> = Vec::new();
 
     for emoji in emoji_list {
-        match upload_emoji(&config, &emoji).await {
+        match upload_emoji(config, &emoji).await {
             Err(err) => {
                 println!("🚫 {} {} {}", &emoji.name, &emoji.path, err);
                 errors.push((format!("{} {}", &emoji.name, &emoji.path), err));
@@ -76,12 +121,6 @@ pub async fn upload(input_path: &Path, name_query: &Option<String>) {
     }
 }
 
-struct UploadConfig {
-    cookie: String,
-    token: String,
-    workspace_name: String,
-}
-
 #[derive(Debug)]
 struct UploadEmoji {
     name: String,
@@ -91,7 +130,8 @@ struct UploadEmoji {
 impl UploadEmoji {
     fn mime_type(&self) -> String {
         let ext = Path::new(&self.path).extension().unwrap().to_str().unwrap();
-        utils::mime_type_from_extension(ext).expect(&format!("⚠️ {} extension not supported", ext))
+        utils::mime_type_from_extension(ext)
+            .unwrap_or_else(|| panic!("⚠️ {} extension not supported", ext))
     }
 }
 
@@ -102,7 +142,7 @@ struct UploadResponseBody {
     error: String,
 }
 
-async fn upload_emoji(config: &UploadConfig, emoji: &UploadEmoji) -> Result<(), Box<dyn Error>> {
+async fn upload_emoji(config: &SlackConfig, emoji: &UploadEmoji) -> Result<(), Box<dyn Error>> {
     let image_file = tokio::fs::File::open(&emoji.path).await?;
 
     let form = multipart::Form::new()
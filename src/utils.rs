@@ -1,13 +1,27 @@
+#[cfg(feature = "network")]
 use std::error::Error;
+#[cfg(feature = "network")]
 use std::fs::File;
-use std::io::{Cursor, Read};
+#[cfg(feature = "network")]
+use std::io::Cursor;
+#[cfg(feature = "network")]
 use std::path::Path;
-use std::time::Duration;
 
-use image::codecs::gif::{GifDecoder, GifEncoder};
-use image::AnimationDecoder;
-use reqwest_middleware::ClientBuilder;
+#[cfg(feature = "network")]
+use reqwest::header::{HeaderValue, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED};
+#[cfg(feature = "network")]
+use reqwest::StatusCode;
+#[cfg(feature = "network")]
+use reqwest_middleware::{ClientBuilder, ClientWithMiddleware};
+#[cfg(feature = "network")]
 use reqwest_retry::{policies::ExponentialBackoff, RetryTransientMiddleware};
+#[cfg(feature = "network")]
+use sha2::{Digest, Sha256};
+
+#[cfg(all(feature = "network", feature = "image"))]
+use crate::animation;
+#[cfg(feature = "network")]
+use crate::manifest::{self, ManifestEntry};
 
 pub fn mime_type_from_extension(ext: &str) -> Option<String> {
     Some(match ext.to_ascii_lowercase().as_str() {
@@ -17,72 +31,109 @@ pub fn mime_type_from_extension(ext: &str) -> Option<String> {
     })
 }
 
-pub fn get_file_as_byte_vec(path: &Path) -> Vec<u8> {
-    let mut file = File::open(&path).expect("no file found");
-    let metadata = std::fs::metadata(&path).expect("unable to read metadata");
-    let mut buffer = vec![0; metadata.len() as usize];
-    file.read(&mut buffer).expect("buffer overflow");
-    buffer
-}
-
-pub async fn download_and_save_image(url: &str, path: &Path) -> Result<File, Box<dyn Error>> {
+#[cfg(feature = "network")]
+pub fn retrying_client() -> ClientWithMiddleware {
     let retry_policy = ExponentialBackoff::builder().build_with_max_retries(3);
-    let client = ClientBuilder::new(reqwest::Client::new())
+    ClientBuilder::new(reqwest::Client::new())
         .with(RetryTransientMiddleware::new_with_policy(retry_policy))
-        .build();
+        .build()
+}
 
+/// Downloads `url` into memory.
+#[cfg(feature = "network")]
+pub async fn fetch_bytes(
+    client: &ClientWithMiddleware,
+    url: &str,
+) -> Result<Vec<u8>, Box<dyn Error>> {
     let res = client.get(url).send().await?;
-
     match res.status() {
-        status if status.is_success() => {
-            let bytes = res.bytes().await?;
-            let mut content = Cursor::new(bytes);
-            let mut file = File::create(path)?;
-            std::io::copy(&mut content, &mut file)?;
-            Ok(file)
-        }
+        status if status.is_success() => Ok(res.bytes().await?.to_vec()),
         status => Err(Box::<dyn Error>::from(status.to_string())),
     }
 }
 
-pub async fn download_and_resize_animation(
+#[cfg(feature = "network")]
+pub enum Fetched {
+    /// the server answered a conditional request with 304 Not Modified
+    Unchanged,
+    Saved(ManifestEntry),
+}
+
+/// Downloads `url` to `path`, sending `If-None-Match`/`If-Modified-Since`
+/// from `cached` when given. The image is written to a `.part` file and
+/// renamed once complete, so an interrupted download never leaves a truncated
+/// file at `path`.
+#[cfg(feature = "network")]
+pub async fn download_and_save_image(
+    client: &ClientWithMiddleware,
     url: &str,
     path: &Path,
-    size: &u32,
-) -> Result<(), Box<dyn Error>> {
-    let retry_policy = ExponentialBackoff::builder().build_with_max_retries(3);
-    let client = ClientBuilder::new(reqwest::Client::new())
-        .with(RetryTransientMiddleware::new_with_policy(retry_policy))
-        .build();
+    cached: Option<&ManifestEntry>,
+) -> Result<Fetched, Box<dyn Error>> {
+    let mut req = client.get(url);
+    if let Some(cached) = cached {
+        if let Some(etag) = &cached.etag {
+            req = req.header(IF_NONE_MATCH, etag);
+        }
+        if let Some(last_modified) = &cached.last_modified {
+            req = req.header(IF_MODIFIED_SINCE, last_modified);
+        }
+    }
+
+    let res = req.send().await?;
 
-    let res = client.get(url).send().await?;
     match res.status() {
+        StatusCode::NOT_MODIFIED if cached.is_some() => Ok(Fetched::Unchanged),
         status if status.is_success() => {
+            let header = |name| {
+                res.headers()
+                    .get(name)
+                    .and_then(|value: &HeaderValue| value.to_str().ok())
+                    .map(|value| value.to_owned())
+            };
+            let etag = header(ETAG);
+            let last_modified = header(LAST_MODIFIED);
+
             let bytes = res.bytes().await?;
-            let content = Cursor::new(bytes);
-            let decoder = GifDecoder::new(content)?;
-            let frames = decoder.into_frames().collect_frames()?;
-            let mut resizes_frames = Vec::new();
-            for frame in &frames {
-                let resized = image::imageops::resize(
-                    &frame.buffer().clone(),
-                    size.to_owned(),
-                    size.to_owned(),
-                    image::imageops::FilterType::Nearest,
-                );
-                resizes_frames.push(image::Frame::from_parts(
-                    resized,
-                    0,
-                    0,
-                    image::Delay::from_saturating_duration(Duration::from_millis(30)),
-                ));
-            }
-            let file = File::create(path)?;
-            let mut encoder = GifEncoder::new(file);
-            encoder.set_repeat(image::codecs::gif::Repeat::Infinite)?;
-            encoder.encode_frames(resizes_frames.into_iter())?;
-            Ok(())
+            let sha256 = format!("{:x}", Sha256::digest(&bytes));
+
+            let mut part_path = path.as_os_str().to_owned();
+            part_path.push(".part");
+            let mut content = Cursor::new(bytes);
+            let mut file = File::create(&part_path)?;
+            let size = std::io::copy(&mut content, &mut file)?;
+            std::fs::rename(&part_path, path)?;
+
+            Ok(Fetched::Saved(ManifestEntry {
+                url: url.to_owned(),
+                etag,
+                last_modified,
+                size,
+                sha256,
+                timestamp: manifest::now(),
+            }))
         }
         status => Err(Box::<dyn Error>::from(status.to_string())),
     }
 }
+
+/// Downloads the animation at `url` and saves it to `path` resized, see
+/// `animation::resize_animation`. Returns what was reduced to fit the budget, if
+/// any.
+#[cfg(all(feature = "network", feature = "image"))]
+pub async fn download_and_resize_animation(
+    client: &ClientWithMiddleware,
+    url: &str,
+    path: &Path,
+    options: &animation::Options,
+) -> Result<Option<animation::Reduction>, Box<dyn Error>> {
+    let bytes = fetch_bytes(client, url).await?;
+    // decoding and encoding is CPU bound, so off the async threads
+    let options = options.clone();
+    let encoded = tokio::task::spawn_blocking(move || {
+        animation::resize_animation(&bytes, &options).map_err(|err| err.to_string())
+    })
+    .await??;
+    std::fs::write(path, encoded.bytes)?;
+    Ok(encoded.reduction)
+}
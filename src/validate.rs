@@ -0,0 +1,88 @@
+use std::collections::{BTreeMap, HashMap};
+use std::error::Error;
+
+use emoji_kitchen::emoji::{self, PairErrorKind};
+
+/// Checks every line of `pairs.txt` and prints unresolved codepoints, invalid
+/// date codes, malformed lines and duplicate lines.
+///
+/// Returns the number of problems found.
+pub fn validate(sources: &emoji::Sources) -> Result<usize, Box<dyn Error>> {
+    let pairs_bytes = sources.pairs()?;
+    let text = std::str::from_utf8(&pairs_bytes)?;
+    let emoji_map = emoji::get_emoji_map(sources)?;
+
+    let (pairs, errors) = emoji::parse_pairs(text, &emoji_map);
+
+    let mut unresolved: BTreeMap<String, Vec<usize>> = BTreeMap::new();
+    let mut invalid_dates = Vec::new();
+    let mut malformed = Vec::new();
+
+    for error in &errors {
+        match &error.kind {
+            PairErrorKind::UnknownCodepoint(side, codepoint) => unresolved
+                .entry(format!("[{}] {}", side, codepoint))
+                .or_default()
+                .push(error.line),
+            PairErrorKind::InvalidDate(_) => invalid_dates.push(error),
+            PairErrorKind::Malformed | PairErrorKind::ExtraFields(_) => malformed.push(error),
+        }
+    }
+
+    let mut first_seen: HashMap<String, usize> = HashMap::new();
+    let mut duplicates = Vec::new();
+
+    for (index, raw) in text.lines().enumerate() {
+        let key = raw.trim().trim_end_matches('/').to_lowercase();
+        if key.is_empty() {
+            continue;
+        }
+        match first_seen.get(&key) {
+            Some(first) => duplicates.push((index + 1, *first, raw)),
+            None => {
+                first_seen.insert(key, index + 1);
+            }
+        }
+    }
+
+    println!("ℹ️ {} pairs parsed", pairs.len());
+
+    if !unresolved.is_empty() {
+        println!("⚠️ {} unresolved codepoints", unresolved.len());
+        for (codepoint, lines) in &unresolved {
+            println!(
+                "  {} on {} lines, first line {}",
+                codepoint,
+                lines.len(),
+                lines[0]
+            );
+        }
+    }
+
+    if !invalid_dates.is_empty() {
+        println!("⚠️ {} invalid date codes", invalid_dates.len());
+        for error in &invalid_dates {
+            println!("  {}", error);
+        }
+    }
+
+    if !malformed.is_empty() {
+        println!("⚠️ {} malformed lines", malformed.len());
+        for error in &malformed {
+            println!("  {}", error);
+        }
+    }
+
+    if !duplicates.is_empty() {
+        println!("⚠️ {} duplicate lines", duplicates.len());
+        for (line, first, raw) in &duplicates {
+            println!("  line {}: duplicate of line {} ({:?})", line, first, raw);
+        }
+    }
+
+    let problems = errors.len() + duplicates.len();
+
+    println!("ℹ️ Completed with {} problems", problems);
+
+    Ok(problems)
+}
@@ -1,82 +1,517 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashSet};
 use std::error::Error;
 use std::fs;
 use std::path::Path;
+use std::time::SystemTime;
 
 use serde::Serialize;
 
 use crate::emoji;
 
+/// Bumped whenever a field of the exported JSON or YAML is renamed, removed
+/// or changes meaning. New fields don't change the version.
+pub const SCHEMA_VERSION: u32 = 1;
+
 #[derive(Serialize)]
-struct JsonEmoji {
-    codepoint: String,
-    name: String,
-    short_name: String,
-    category: String,
-    subcategory: String,
-    sort_order: u16,
-    skin_variations: HashMap<String, String>,
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct ExportEmoji {
+    /// lowercase, `-` separated, e.g. `2764-fe0f`
+    pub codepoint: String,
+    pub name: String,
+    pub short_name: String,
+    pub category: String,
+    pub subcategory: String,
+    pub sort_order: u16,
+    /// skin tone modifiers (e.g. `1f3fb`) to the codepoint of that variant
+    pub skin_variations: BTreeMap<String, String>,
 }
 
 #[derive(Serialize)]
-struct JsonEmojiPair {
-    name: String,
-    src: String,
-    sort_order: u16,
-    d: String,
-    base: JsonEmoji,
-    pair: JsonEmoji,
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct ExportPair {
+    /// `<base short name>_<pair short name>`, with `_<date>` appended for
+    /// older revisions
+    pub name: String,
+    pub image_url: String,
+    /// the hex date code from `pairs.txt`
+    pub date_code: String,
+    /// the `YYYYMMDD` date the artwork was published
+    pub date: i64,
+    /// 0 for the newest artwork of this base and pair, 1 for the one before, ...
+    pub revision: usize,
+    pub sort_order: u16,
+    pub base: ExportEmoji,
+    pub pair: ExportEmoji,
 }
 
-pub async fn json(pairs: Vec<emoji::EmojiPair>, output: &Path) -> Result<(), Box<dyn Error>> {
-    if let Some(output_path) = output.parent() {
-        fs::create_dir_all(output_path).expect(&format!(
-            "could not create output dir: {}",
-            output.display()
-        ));
+#[derive(Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct Generator {
+    pub name: String,
+    pub version: String,
+}
+
+#[derive(Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct Source {
+    /// of the `pairs.txt` the export was made from
+    pub pairs_sha256: String,
+    /// the newest `YYYYMMDD` date in that `pairs.txt`
+    pub pairs_latest_date: i64,
+}
+
+#[derive(Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct Counts {
+    pub pairs: usize,
+    /// distinct emoji in `pairs`
+    pub emoji: usize,
+    /// lines of `pairs.txt` that couldn't be loaded
+    pub skipped_lines: usize,
+}
+
+/// Where an export came from, at the top of the JSON and YAML exports and the
+/// index.
+#[derive(Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct Header {
+    /// bumped when a field is renamed, removed or changes meaning
+    pub schema_version: u32,
+    pub generator: Generator,
+    pub source: Source,
+    /// RFC 3339, in UTC
+    pub generated_at: String,
+    pub counts: Counts,
+}
+
+/// The JSON and YAML export: a header describing where the data came from,
+/// then the pairs.
+#[derive(Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct Export {
+    #[serde(flatten)]
+    pub header: Header,
+    pub pairs: Vec<ExportPair>,
+}
+
+/// A combination in the index: the partner's codepoint and the date code,
+/// serialized as a two element array.
+#[derive(Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct Partner(pub String, pub String);
+
+/// One base's file of a sharded index.
+#[derive(Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct Shard {
+    /// the index's `schema_version`
+    pub schema_version: u32,
+    /// base codepoint
+    pub base: String,
+    pub pairs: Vec<Partner>,
+}
+
+/// The indexed export: each emoji once, and the combinations keyed by base
+/// codepoint.
+#[derive(Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct Index {
+    #[serde(flatten)]
+    pub header: Header,
+    /// by codepoint
+    pub emoji: BTreeMap<String, ExportEmoji>,
+    /// base codepoint to its partners, when not sharded
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pairs: Option<BTreeMap<String, Vec<Partner>>>,
+    /// base codepoint to the file with its partners, when sharded
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub shards: Option<BTreeMap<String, String>>,
+}
+
+impl From<&emoji::Emoji> for ExportEmoji {
+    fn from(emoji: &emoji::Emoji) -> Self {
+        ExportEmoji {
+            codepoint: emoji.codepoint.to_string(),
+            name: emoji.name.to_string(),
+            short_name: emoji.short_name.to_string(),
+            category: emoji.category.to_string(),
+            subcategory: emoji.subcategory.to_string(),
+            sort_order: emoji.sort_order,
+            skin_variations: emoji
+                .skin_variations
+                .iter()
+                .map(|(k, v)| (k.to_lowercase(), v.codepoint.to_owned()))
+                .collect(),
+        }
     }
+}
 
-    let emoji_data: Vec<JsonEmojiPair> = pairs
-        .iter()
-        .map(|e| JsonEmojiPair {
+impl From<&emoji::EmojiPair> for ExportPair {
+    fn from(e: &emoji::EmojiPair) -> Self {
+        ExportPair {
             name: e.name.to_string(),
-            src: e.image_url.to_string(),
+            image_url: e.image_url.to_string(),
+            date_code: e.d.to_string(),
+            date: e.date(),
+            revision: e.revision,
             sort_order: e.sort_order,
-            d: e.d.to_string(),
-            base: JsonEmoji {
-                codepoint: e.base.codepoint.to_string(),
-                name: e.base.name.to_string(),
-                short_name: e.base.short_name.to_string(),
-                category: e.base.category.to_string(),
-                subcategory: e.base.subcategory.to_string(),
-                sort_order: e.base.sort_order,
-                skin_variations: e
-                    .base
-                    .skin_variations
-                    .iter()
-                    .map(|(k, v)| (k.to_owned(), v.codepoint.to_owned()))
-                    .collect(),
+            base: ExportEmoji::from(&e.base),
+            pair: ExportEmoji::from(&e.pair),
+        }
+    }
+}
+
+impl Header {
+    pub fn new(db: &emoji::EmojiDB) -> Self {
+        let emoji: HashSet<&str> = db
+            .pairs()
+            .iter()
+            .flat_map(|pair| [pair.base.codepoint.as_str(), pair.pair.codepoint.as_str()])
+            .collect();
+        Header {
+            schema_version: SCHEMA_VERSION,
+            generator: Generator {
+                name: env!("CARGO_PKG_NAME").to_owned(),
+                version: env!("CARGO_PKG_VERSION").to_owned(),
+            },
+            source: Source {
+                pairs_sha256: db.revision.sha256.to_owned(),
+                pairs_latest_date: db.revision.latest_date,
             },
-            pair: JsonEmoji {
-                codepoint: e.pair.codepoint.to_string(),
-                name: e.pair.name.to_string(),
-                short_name: e.pair.short_name.to_string(),
-                category: e.pair.category.to_string(),
-                subcategory: e.pair.subcategory.to_string(),
-                sort_order: e.pair.sort_order,
-                skin_variations: e
-                    .pair
-                    .skin_variations
-                    .iter()
-                    .map(|(k, v)| (k.to_owned(), v.codepoint.to_owned()))
-                    .collect(),
+            generated_at: humantime::format_rfc3339_seconds(SystemTime::now()).to_string(),
+            counts: Counts {
+                pairs: db.pairs().len(),
+                emoji: emoji.len(),
+                skipped_lines: db.errors.len(),
             },
-        })
-        .collect();
+        }
+    }
+}
 
-    let json = serde_json::to_string_pretty(&emoji_data)?;
+impl Export {
+    pub fn new(db: &emoji::EmojiDB) -> Self {
+        Export {
+            header: Header::new(db),
+            pairs: db.pairs().iter().map(ExportPair::from).collect(),
+        }
+    }
+}
 
-    fs::write(output, json)?;
+impl Index {
+    /// The index of `db`, with the partners inline or, when `shards`, only
+    /// the shard filenames. `partners_by_base` has the partners either way.
+    pub fn new(db: &emoji::EmojiDB, shards: bool) -> Self {
+        let mut emoji = BTreeMap::new();
+        for pair in db.pairs() {
+            for e in [&pair.base, &pair.pair] {
+                if !emoji.contains_key(&e.codepoint) {
+                    emoji.insert(e.codepoint.to_owned(), ExportEmoji::from(e));
+                }
+            }
+        }
+        let partners = Self::partners_by_base(db);
+        let (pairs, shards) = if shards {
+            let files = partners
+                .keys()
+                .map(|base| (base.to_owned(), Self::shard_filename(base)))
+                .collect();
+            (None, Some(files))
+        } else {
+            (Some(partners), None)
+        };
+        Index {
+            header: Header::new(db),
+            emoji,
+            pairs,
+            shards,
+        }
+    }
+
+    /// Base codepoint to its partners, in pair `sort_order` (ties in `pairs.txt` order).
+    pub fn partners_by_base(db: &emoji::EmojiDB) -> BTreeMap<String, Vec<Partner>> {
+        let mut partners: BTreeMap<String, Vec<Partner>> = BTreeMap::new();
+        for pair in db.pairs() {
+            partners
+                .entry(pair.base.codepoint.to_owned())
+                .or_default()
+                .push(Partner(pair.pair.codepoint.to_owned(), pair.d.to_owned()));
+        }
+        partners
+    }
+
+    pub fn shard_filename(base: &str) -> String {
+        format!("{}.json", base)
+    }
+}
 
+/// The JSON Schema of `Export`.
+#[cfg(feature = "schema")]
+pub fn json_schema() -> String {
+    let schema = schemars::schema_for!(Export);
+    serde_json::to_string_pretty(&schema).expect("schemas always serialize")
+}
+
+/// What `export` writes.
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Format {
+    /// pretty-printed JSON, see `Export`
+    #[default]
+    Json,
+    /// the same JSON on a single line
+    JsonCompact,
+    /// one `ExportPair` per line, without the header
+    Ndjson,
+    /// one row per pair, with the base and pair columns flattened
+    Csv,
+    /// the same document as JSON
+    Yaml,
+    /// compact JSON with each emoji once and the pairs keyed by base, see
+    /// `Index`
+    Indexed,
+    /// a database with `emoji`, `skin_variations` and `pairs` tables
+    Sqlite,
+}
+
+fn create_parent_dir(output: &Path) -> Result<(), Box<dyn Error>> {
+    if let Some(output_path) = output.parent() {
+        fs::create_dir_all(output_path)?;
+    }
+    Ok(())
+}
+
+pub async fn json(db: &emoji::EmojiDB, output: &Path) -> Result<(), Box<dyn Error>> {
+    export(db, output, Format::Json).await
+}
+
+/// Writes the pairs of `db` to `output` in `format`. CSV and YAML need the
+/// `export` feature, SQLite the `sqlite` feature.
+pub async fn export(
+    db: &emoji::EmojiDB,
+    output: &Path,
+    format: Format,
+) -> Result<(), Box<dyn Error>> {
+    create_parent_dir(output)?;
+
+    match format {
+        Format::Json => fs::write(output, serde_json::to_string_pretty(&Export::new(db))?)?,
+        Format::JsonCompact => fs::write(output, serde_json::to_string(&Export::new(db))?)?,
+        Format::Ndjson => {
+            let mut ndjson = String::new();
+            for pair in db.pairs() {
+                ndjson.push_str(&serde_json::to_string(&ExportPair::from(pair))?);
+                ndjson.push('\n');
+            }
+            fs::write(output, ndjson)?;
+        }
+        Format::Csv => csv(db.pairs(), output)?,
+        Format::Yaml => yaml(&Export::new(db), output)?,
+        Format::Indexed => fs::write(output, serde_json::to_string(&Index::new(db, false))?)?,
+        Format::Sqlite => sqlite(db.pairs(), output)?,
+    }
+
+    Ok(())
+}
+
+/// Writes the indexed export split for lazy loading: `index.json` in
+/// `output_dir` with every emoji and the shard of each base, and one
+/// `<base codepoint>.json` shard per base with its partners, see `Shard`.
+pub async fn indexed_shards(db: &emoji::EmojiDB, output_dir: &Path) -> Result<(), Box<dyn Error>> {
+    fs::create_dir_all(output_dir)?;
+    for (base, pairs) in Index::partners_by_base(db) {
+        let path = output_dir.join(Index::shard_filename(&base));
+        let shard = Shard {
+            schema_version: SCHEMA_VERSION,
+            base,
+            pairs,
+        };
+        fs::write(path, serde_json::to_string(&shard)?)?;
+    }
+    fs::write(
+        output_dir.join("index.json"),
+        serde_json::to_string(&Index::new(db, true))?,
+    )?;
+    Ok(())
+}
+
+#[cfg(feature = "export")]
+#[derive(Serialize)]
+struct CsvEmojiPair<'a> {
+    name: &'a str,
+    image_url: &'a str,
+    sort_order: u16,
+    date_code: &'a str,
+    date: i64,
+    base_codepoint: &'a str,
+    base_name: &'a str,
+    base_short_name: &'a str,
+    base_category: &'a str,
+    base_subcategory: &'a str,
+    base_sort_order: u16,
+    pair_codepoint: &'a str,
+    pair_name: &'a str,
+    pair_short_name: &'a str,
+    pair_category: &'a str,
+    pair_subcategory: &'a str,
+    pair_sort_order: u16,
+}
+
+#[cfg(feature = "export")]
+fn csv(pairs: &[emoji::EmojiPair], output: &Path) -> Result<(), Box<dyn Error>> {
+    let mut writer = csv::Writer::from_path(output)?;
+    for e in pairs {
+        writer.serialize(CsvEmojiPair {
+            name: &e.name,
+            image_url: &e.image_url,
+            sort_order: e.sort_order,
+            date_code: &e.d,
+            date: e.date(),
+            base_codepoint: &e.base.codepoint,
+            base_name: &e.base.name,
+            base_short_name: &e.base.short_name,
+            base_category: &e.base.category,
+            base_subcategory: &e.base.subcategory,
+            base_sort_order: e.base.sort_order,
+            pair_codepoint: &e.pair.codepoint,
+            pair_name: &e.pair.name,
+            pair_short_name: &e.pair.short_name,
+            pair_category: &e.pair.category,
+            pair_subcategory: &e.pair.subcategory,
+            pair_sort_order: e.pair.sort_order,
+        })?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+#[cfg(not(feature = "export"))]
+fn csv(_: &[emoji::EmojiPair], _: &Path) -> Result<(), Box<dyn Error>> {
+    Err("CSV exports need the `export` feature".into())
+}
+
+#[cfg(feature = "export")]
+fn yaml(export: &Export, output: &Path) -> Result<(), Box<dyn Error>> {
+    fs::write(output, serde_yaml::to_string(export)?)?;
     Ok(())
 }
+
+#[cfg(not(feature = "export"))]
+fn yaml(_: &Export, _: &Path) -> Result<(), Box<dyn Error>> {
+    Err("YAML exports need the `export` feature".into())
+}
+
+#[cfg(feature = "sqlite")]
+const SQLITE_SCHEMA: &str = "
+CREATE TABLE emoji (
+    codepoint TEXT PRIMARY KEY,
+    name TEXT NOT NULL,
+    short_name TEXT NOT NULL,
+    category TEXT NOT NULL,
+    subcategory TEXT NOT NULL,
+    sort_order INTEGER NOT NULL
+);
+CREATE TABLE skin_variations (
+    emoji TEXT NOT NULL REFERENCES emoji (codepoint),
+    skin_tone TEXT NOT NULL,
+    codepoint TEXT NOT NULL,
+    PRIMARY KEY (emoji, skin_tone)
+);
+CREATE TABLE pairs (
+    id INTEGER PRIMARY KEY,
+    name TEXT NOT NULL,
+    image_url TEXT NOT NULL,
+    sort_order INTEGER NOT NULL,
+    date_code TEXT NOT NULL,
+    date INTEGER NOT NULL,
+    base TEXT NOT NULL REFERENCES emoji (codepoint),
+    pair TEXT NOT NULL REFERENCES emoji (codepoint)
+);
+CREATE INDEX pairs_name ON pairs (name);
+CREATE INDEX pairs_base ON pairs (base);
+CREATE INDEX pairs_pair ON pairs (pair);
+";
+
+/// Replaces `output` with a database of `pairs`, storing each emoji once.
+#[cfg(feature = "sqlite")]
+fn sqlite(pairs: &[emoji::EmojiPair], output: &Path) -> Result<(), Box<dyn Error>> {
+    use rusqlite::{params, Connection};
+
+    match fs::remove_file(output) {
+        Err(err) if err.kind() != std::io::ErrorKind::NotFound => return Err(err.into()),
+        _ => {}
+    }
+
+    let mut conn = Connection::open(output)?;
+    conn.execute_batch(SQLITE_SCHEMA)?;
+
+    let tx = conn.transaction()?;
+    {
+        let mut insert_emoji = tx.prepare(
+            "INSERT OR IGNORE INTO emoji (codepoint, name, short_name, category, subcategory, sort_order)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        )?;
+        let mut insert_variation = tx.prepare(
+            "INSERT OR IGNORE INTO skin_variations (emoji, skin_tone, codepoint) VALUES (?1, ?2, ?3)",
+        )?;
+        let mut insert_pair = tx.prepare(
+            "INSERT INTO pairs (name, image_url, sort_order, date_code, date, base, pair)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        )?;
+
+        for e in pairs {
+            for emoji in [&e.base, &e.pair] {
+                let inserted = insert_emoji.execute(params![
+                    emoji.codepoint,
+                    emoji.name,
+                    emoji.short_name,
+                    emoji.category,
+                    emoji.subcategory,
+                    emoji.sort_order,
+                ])?;
+                if inserted == 0 {
+                    continue;
+                }
+                for (skin_tone, variation) in &emoji.skin_variations {
+                    insert_variation.execute(params![
+                        emoji.codepoint,
+                        skin_tone.to_lowercase(),
+                        variation.codepoint
+                    ])?;
+                }
+            }
+            insert_pair.execute(params![
+                e.name,
+                e.image_url,
+                e.sort_order,
+                e.d,
+                e.date(),
+                e.base.codepoint,
+                e.pair.codepoint,
+            ])?;
+        }
+    }
+    tx.commit()?;
+
+    Ok(())
+}
+
+#[cfg(not(feature = "sqlite"))]
+fn sqlite(_: &[emoji::EmojiPair], _: &Path) -> Result<(), Box<dyn Error>> {
+    Err("SQLite exports need the `sqlite` feature".into())
+}
+
+#[cfg(all(test, feature = "schema"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn schema_file_matches_export() {
+        let file: serde_json::Value =
+            serde_json::from_str(include_str!("../schema/export.v1.json")).unwrap();
+        let generated = serde_json::to_value(schemars::schema_for!(Export)).unwrap();
+        assert_eq!(
+            file, generated,
+            "schema/export.v1.json is out of date, regenerate it with `json --export-schema`"
+        );
+    }
+}